@@ -1,14 +1,46 @@
 use anyhow::Result;
-use beatrice_client::{configuration::Configuration, shell::Shell};
-use beatrice_proto::beatrice::beatrice_client::BeatriceClient;
+use beatrice_client::{
+    configuration::Configuration,
+    shell::{OutputFormat, ScriptPrompter, Shell},
+};
+use beatrice_proto::{admin::admin_client::AdminClient, beatrice::beatrice_client::BeatriceClient};
 use clap::{AppSettings, Clap};
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+    process,
+};
+use tonic::transport::Endpoint;
 use tracing_subscriber::{fmt::format::DefaultFields, prelude::*, EnvFilter};
+
+/// Exits with 1 if any command fails.
 #[derive(Clap)]
 #[clap(setting = AppSettings::ColoredHelp)]
 struct Opts {
+    /// YAML file of the configuration
+    #[clap(long)]
+    conf: Option<String>,
+    /// Overrides a field of the configuration, e.g. `--set admin=http://127.0.0.1:50100`.
+    /// Takes precedence over the file and `BEATRICE_*` environment variables
+    #[clap(long, number_of_values = 1)]
+    set: Vec<String>,
+    /// Prints the effective configuration and exits
     #[clap(long)]
-    conf: String,
+    print_config: bool,
+    /// Runs the commands separated by newlines and exits
+    #[clap(short, long, conflicts_with = "file")]
+    execute: Option<String>,
+    /// Runs the commands in the file and exits
+    #[clap(short, long)]
+    file: Option<String>,
+    /// Reads commands from stdin without prompts
+    #[clap(short, long)]
+    quiet: bool,
+    /// Format of the results: raw, json or table
+    #[clap(short, long, default_value = "raw")]
+    output: OutputFormat,
 }
 
 fn init_tracing_subscriber() {
@@ -19,31 +51,64 @@ fn init_tracing_subscriber() {
         .try_init();
 }
 
-fn load_conf<P: AsRef<Path>>(path: P) -> Result<Configuration> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let conf = serde_yaml::from_reader(reader)?;
-    Ok(conf)
+fn load_conf(opts: &Opts) -> Result<Configuration> {
+    Configuration::load(opts.conf.as_deref().map(Path::new), env::vars(), &opts.set)
 }
 
-async fn run(conf_path: &String) -> Result<()> {
-    let conf = load_conf(&conf_path)?;
+async fn run(opts: &Opts) -> Result<usize> {
+    let conf = load_conf(opts)?;
+    if opts.print_config {
+        print!("{}", serde_yaml::to_string(&conf)?);
+        return Ok(0);
+    }
+    let admin = match conf.admin {
+        Some(addr) => Some(AdminClient::new(
+            Endpoint::from_shared(addr)?.connect_lazy()?,
+        )),
+        None => None,
+    };
     let client = BeatriceClient::from_conf(conf.repc)?;
-    let shell = Shell::new(client);
-    shell.run().await
+    if let Some(commands) = &opts.execute {
+        let prompter = ScriptPrompter::new(commands.as_bytes());
+        Shell::with_prompter(client, admin, prompter)
+            .with_format(opts.output)
+            .run()
+            .await
+    } else if let Some(path) = &opts.file {
+        let prompter = ScriptPrompter::new(BufReader::new(File::open(path)?));
+        Shell::with_prompter(client, admin, prompter)
+            .with_format(opts.output)
+            .run()
+            .await
+    } else if opts.quiet {
+        let prompter = ScriptPrompter::new(BufReader::new(io::stdin()));
+        Shell::with_prompter(client, admin, prompter)
+            .with_format(opts.output)
+            .run()
+            .await
+    } else {
+        Shell::new(client, admin)
+            .with_format(opts.output)
+            .run()
+            .await
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let Opts { conf } = Opts::parse();
+    let opts = Opts::parse();
 
     init_tracing_subscriber();
 
-    run(&conf).await.map_err(|e| {
+    let failures = run(&opts).await.map_err(|e| {
         tracing::error!(
             error = <String as AsRef<str>>::as_ref(&e.to_string()),
             "failed to run"
         );
         e
-    })
+    })?;
+    if failures > 0 {
+        process::exit(1);
+    }
+    Ok(())
 }
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use beatrice_client::{
+    bulk::{self, Codec, Format, ImportOptions, RecordReader, RecordWriter, RowRange},
+    configuration::Configuration,
+    Client,
+};
+use bytes::Bytes;
+use clap::{AppSettings, Clap};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::Instant,
+};
+use tracing_subscriber::{fmt::format::DefaultFields, prelude::*, EnvFilter};
+
+#[derive(Clap)]
+#[clap(setting = AppSettings::ColoredHelp)]
+struct Opts {
+    #[clap(long)]
+    conf: String,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Clap)]
+enum Command {
+    /// Writes the records in a file
+    Import(ImportOpts),
+    /// Writes the latest version of each row to a file
+    Export(ExportOpts),
+}
+
+#[derive(Clap)]
+struct ImportOpts {
+    /// File to import, or `-` for stdin
+    input: String,
+    /// Format of the file: csv, tsv or jsonl
+    #[clap(long, default_value = "csv")]
+    format: Format,
+    /// Encoding of rows and values: utf8, hex or base64
+    #[clap(long, default_value = "utf8")]
+    encoding: Codec,
+    /// Skips the first line of CSV and TSV files
+    #[clap(long)]
+    header: bool,
+    /// Number of records written by each batch
+    #[clap(long, default_value = "1000")]
+    batch_size: usize,
+    /// Number of batches written in parallel.
+    /// Records without timestamps are given the time their batch is applied,
+    /// so with more than 1 the latest version of a row repeated in the input may not be its last
+    #[clap(long, default_value = "4")]
+    parallelism: usize,
+    /// Reports the number of the written records to stderr
+    #[clap(long)]
+    progress: bool,
+}
+
+#[derive(Clap)]
+struct ExportOpts {
+    /// File to export to. Defaults to stdout
+    output: Option<String>,
+    /// Format of the file: csv, tsv or jsonl
+    #[clap(long, default_value = "csv")]
+    format: Format,
+    /// Encoding of rows and values: utf8, hex or base64
+    #[clap(long, default_value = "utf8")]
+    encoding: Codec,
+    /// Writes a header line to CSV and TSV files
+    #[clap(long)]
+    header: bool,
+    /// Exports the rows starting with the prefix
+    #[clap(long, default_value = "")]
+    prefix: String,
+    /// Exports the rows equal to or greater than the row
+    #[clap(long)]
+    start: Option<String>,
+    /// Exports the rows less than the row
+    #[clap(long)]
+    end: Option<String>,
+    /// Reports the number of the written records to stderr
+    #[clap(long)]
+    progress: bool,
+}
+
+fn init_tracing_subscriber() {
+    let formatter = DefaultFields::new().delimited(",");
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .fmt_fields(formatter)
+        .try_init();
+}
+
+fn load_conf<P: AsRef<Path>>(path: P) -> Result<Configuration> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+    let conf = serde_yaml::from_reader(reader)?;
+    Ok(conf)
+}
+
+/// Reports progress to stderr on a single line.
+struct Progress {
+    enabled: bool,
+    start: Instant,
+}
+
+impl Progress {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            start: Instant::now(),
+        }
+    }
+
+    fn report(&self, records: usize) {
+        if self.enabled {
+            let secs = self.start.elapsed().as_secs_f64();
+            eprint!(
+                "\r{} records in {:.1}s ({:.0} records/s)",
+                records,
+                secs,
+                records as f64 / secs.max(1e-3)
+            );
+        }
+    }
+
+    fn finish(&self) {
+        if self.enabled {
+            eprintln!();
+        }
+    }
+}
+
+async fn import(client: &Client, opts: &ImportOpts) -> Result<()> {
+    let input: Box<dyn Read> = match opts.input.as_str() {
+        "-" => Box::new(io::stdin()),
+        path => Box::new(File::open(path)?),
+    };
+    let records = RecordReader::new(input, opts.format, opts.encoding, opts.header);
+    let options = ImportOptions {
+        batch_size: opts.batch_size,
+        parallelism: opts.parallelism,
+    };
+    let progress = Progress::new(opts.progress);
+    let res = bulk::import(client, records, &options, |n| progress.report(n)).await;
+    progress.finish();
+    res?;
+    Ok(())
+}
+
+async fn export(client: &Client, opts: &ExportOpts) -> Result<()> {
+    let output: Box<dyn Write> = match &opts.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    let mut writer = RecordWriter::new(
+        BufWriter::new(output),
+        opts.format,
+        opts.encoding,
+        opts.header,
+    )?;
+    let range = RowRange {
+        prefix: Bytes::from(opts.prefix.clone()),
+        start: opts.start.clone().map(Bytes::from),
+        end: opts.end.clone().map(Bytes::from),
+    };
+    let progress = Progress::new(opts.progress);
+    let res = bulk::export(client, &range, &mut writer, |n| progress.report(n)).await;
+    progress.finish();
+    res?;
+    Ok(())
+}
+
+async fn run(opts: &Opts) -> Result<()> {
+    let conf = load_conf(&opts.conf)?;
+    let client = Client::from_conf(conf.repc)?;
+    match &opts.command {
+        Command::Import(opts) => import(&client, opts).await,
+        Command::Export(opts) => export(&client, opts).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opts = Opts::parse();
+
+    init_tracing_subscriber();
+
+    run(&opts).await.map_err(|e| {
+        tracing::error!(
+            error = <String as AsRef<str>>::as_ref(&e.to_string()),
+            "failed to run"
+        );
+        e
+    })
+}
@@ -0,0 +1,495 @@
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::{
+    error, fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    str::{self, FromStr},
+};
+
+/// Format of the files imported and exported
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// `row,value` or `row,timestamp,value` per line
+    Csv,
+    /// Same as `Csv` but separated by tabs
+    Tsv,
+    /// A JSON object with `row`, `value` and optional `timestamp` per line
+    Jsonl,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err(format!("unknown format {}", s)),
+        }
+    }
+}
+
+impl Format {
+    fn delimiter(&self) -> u8 {
+        match self {
+            Format::Tsv => b'\t',
+            _ => b',',
+        }
+    }
+}
+
+/// Encoding of rows and values in the files
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    /// Bytes as they are. Exporting fails on bytes which are not UTF-8.
+    Utf8,
+    Hex,
+    Base64,
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Codec::Utf8
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(Codec::Utf8),
+            "hex" => Ok(Codec::Hex),
+            "base64" => Ok(Codec::Base64),
+            _ => Err(format!("unknown encoding {}", s)),
+        }
+    }
+}
+
+impl Codec {
+    fn decode(&self, field: &[u8]) -> Option<Bytes> {
+        match self {
+            Codec::Utf8 => Some(Bytes::copy_from_slice(field)),
+            Codec::Hex => decode_hex(field).map(Bytes::from),
+            Codec::Base64 => base64::decode(field).ok().map(Bytes::from),
+        }
+    }
+
+    fn encode(&self, v: &[u8]) -> Option<String> {
+        match self {
+            Codec::Utf8 => str::from_utf8(v).ok().map(|s| s.to_string()),
+            Codec::Hex => Some(v.iter().map(|b| format!("{:02x}", b)).collect()),
+            Codec::Base64 => Some(base64::encode(v)),
+        }
+    }
+}
+
+fn decode_hex(s: &[u8]) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    s.chunks(2)
+        .map(|c| u8::from_str_radix(str::from_utf8(c).ok()?, 16).ok())
+        .collect()
+}
+
+/// A version of a row in a file. Imported records without timestamps are
+/// written at the current time of the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub row: Bytes,
+    pub timestamp: Option<u64>,
+    pub value: Bytes,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonRecord {
+    row: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+    value: String,
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Json {
+        line: u64,
+        e: serde_json::Error,
+    },
+    /// A line does not have 2 or 3 fields
+    Fields {
+        line: u64,
+        n: usize,
+    },
+    Timestamp {
+        line: u64,
+    },
+    Decode {
+        line: u64,
+        field: &'static str,
+    },
+    /// A row or a value cannot be encoded, e.g. non-UTF-8 bytes with `Codec::Utf8`
+    Encode {
+        row: Bytes,
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(e) => write!(f, "{}", e),
+            FormatError::Csv(e) => write!(f, "{}", e),
+            FormatError::Json { line, e } => write!(f, "line {}: {}", line, e),
+            FormatError::Fields { line, n } => {
+                write!(f, "line {}: expected 2 or 3 fields, but got {}", line, n)
+            }
+            FormatError::Timestamp { line } => write!(f, "line {}: invalid timestamp", line),
+            FormatError::Decode { line, field } => {
+                write!(f, "line {}: cannot decode {}", line, field)
+            }
+            FormatError::Encode { row, field } => write!(
+                f,
+                "cannot encode {} of row {:?}, try another encoding",
+                field, row
+            ),
+        }
+    }
+}
+
+impl error::Error for FormatError {}
+
+impl From<io::Error> for FormatError {
+    fn from(e: io::Error) -> Self {
+        FormatError::Io(e)
+    }
+}
+
+impl From<csv::Error> for FormatError {
+    fn from(e: csv::Error) -> Self {
+        FormatError::Csv(e)
+    }
+}
+
+/// Reads records from a file in order.
+pub struct RecordReader<R> {
+    inner: ReaderInner<R>,
+    codec: Codec,
+}
+
+enum ReaderInner<R> {
+    Csv(csv::Reader<R>),
+    Jsonl {
+        lines: io::Lines<BufReader<R>>,
+        line: u64,
+    },
+}
+
+impl<R> RecordReader<R>
+where
+    R: Read,
+{
+    /// Skips the first line of CSV and TSV files if `header` is `true`.
+    pub fn new(reader: R, format: Format, codec: Codec, header: bool) -> Self {
+        let inner = match format {
+            Format::Csv | Format::Tsv => ReaderInner::Csv(
+                csv::ReaderBuilder::new()
+                    .delimiter(format.delimiter())
+                    .has_headers(header)
+                    .flexible(true)
+                    .from_reader(reader),
+            ),
+            Format::Jsonl => ReaderInner::Jsonl {
+                lines: BufReader::new(reader).lines(),
+                line: 0,
+            },
+        };
+        Self { inner, codec }
+    }
+
+    fn decode(&self, field: &[u8], line: u64, name: &'static str) -> Result<Bytes, FormatError> {
+        self.codec
+            .decode(field)
+            .ok_or(FormatError::Decode { line, field: name })
+    }
+
+    fn read_csv(&mut self) -> Option<Result<Record, FormatError>> {
+        let reader = match &mut self.inner {
+            ReaderInner::Csv(reader) => reader,
+            _ => unreachable!(),
+        };
+        let mut record = csv::ByteRecord::new();
+        match reader.read_byte_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(e) => return Some(Err(e.into())),
+        }
+        let line = record.position().map_or(0, |p| p.line());
+        let (row, timestamp, value) = match record.len() {
+            2 => (&record[0], None, &record[1]),
+            3 => (&record[0], Some(&record[1]), &record[2]),
+            n => return Some(Err(FormatError::Fields { line, n })),
+        };
+        let timestamp = match timestamp {
+            None | Some([]) => None,
+            Some(t) => match str::from_utf8(t).ok().and_then(|t| t.parse().ok()) {
+                Some(t) => Some(t),
+                None => return Some(Err(FormatError::Timestamp { line })),
+            },
+        };
+        Some(self.decode(row, line, "row").and_then(|row| {
+            self.decode(value, line, "value").map(|value| Record {
+                row,
+                timestamp,
+                value,
+            })
+        }))
+    }
+
+    fn read_jsonl(&mut self) -> Option<Result<Record, FormatError>> {
+        let (lines, line) = match &mut self.inner {
+            ReaderInner::Jsonl { lines, line } => (lines, line),
+            _ => unreachable!(),
+        };
+        let text = loop {
+            *line += 1;
+            match lines.next()? {
+                Ok(text) if text.trim().is_empty() => continue,
+                Ok(text) => break text,
+                Err(e) => return Some(Err(e.into())),
+            }
+        };
+        let line = *line;
+        let record = match serde_json::from_str::<JsonRecord>(&text) {
+            Ok(record) => record,
+            Err(e) => return Some(Err(FormatError::Json { line, e })),
+        };
+        Some(
+            self.decode(record.row.as_bytes(), line, "row")
+                .and_then(|row| {
+                    self.decode(record.value.as_bytes(), line, "value")
+                        .map(|value| Record {
+                            row,
+                            timestamp: record.timestamp,
+                            value,
+                        })
+                }),
+        )
+    }
+}
+
+impl<R> Iterator for RecordReader<R>
+where
+    R: Read,
+{
+    type Item = Result<Record, FormatError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner {
+            ReaderInner::Csv(_) => self.read_csv(),
+            ReaderInner::Jsonl { .. } => self.read_jsonl(),
+        }
+    }
+}
+
+/// Writes records to a file with their timestamps.
+pub struct RecordWriter<W: Write> {
+    inner: WriterInner<W>,
+    codec: Codec,
+}
+
+enum WriterInner<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Jsonl(W),
+}
+
+impl<W> RecordWriter<W>
+where
+    W: Write,
+{
+    /// Writes `row,timestamp,value` as the first line of CSV and TSV files if `header` is `true`.
+    pub fn new(writer: W, format: Format, codec: Codec, header: bool) -> Result<Self, FormatError> {
+        let inner = match format {
+            Format::Csv | Format::Tsv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .delimiter(format.delimiter())
+                    .from_writer(writer);
+                if header {
+                    writer.write_record(&["row", "timestamp", "value"])?;
+                }
+                WriterInner::Csv(Box::new(writer))
+            }
+            Format::Jsonl => WriterInner::Jsonl(writer),
+        };
+        Ok(Self { inner, codec })
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<(), FormatError> {
+        let encode = |v: &[u8], field| {
+            self.codec.encode(v).ok_or_else(|| FormatError::Encode {
+                row: record.row.clone(),
+                field,
+            })
+        };
+        let row = encode(&record.row, "row")?;
+        let value = encode(&record.value, "value")?;
+        match &mut self.inner {
+            WriterInner::Csv(writer) => {
+                let timestamp = record.timestamp.map_or_else(String::new, |t| t.to_string());
+                writer.write_record(&[row, timestamp, value])?;
+            }
+            WriterInner::Jsonl(writer) => {
+                let record = JsonRecord {
+                    row,
+                    timestamp: record.timestamp,
+                    value,
+                };
+                serde_json::to_writer(&mut *writer, &record).map_err(io::Error::from)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), FormatError> {
+        match &mut self.inner {
+            WriterInner::Csv(writer) => writer.flush()?,
+            WriterInner::Jsonl(writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(row: &'static [u8], timestamp: Option<u64>, value: &'static [u8]) -> Record {
+        Record {
+            row: Bytes::from_static(row),
+            timestamp,
+            value: Bytes::from_static(value),
+        }
+    }
+
+    fn read(input: &str, format: Format, codec: Codec) -> Vec<Result<Record, FormatError>> {
+        RecordReader::new(input.as_bytes(), format, codec, false).collect()
+    }
+
+    fn write(records: &[Record], format: Format, codec: Codec, header: bool) -> String {
+        let mut out = vec![];
+        let mut writer = RecordWriter::new(&mut out, format, codec, header).unwrap();
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_read() {
+        let records = read("a,1\n\"b,c\",2,\"x\"\"y\"\nd,,\n", Format::Csv, Codec::Utf8);
+        assert_eq!(
+            vec![
+                record(b"a", None, b"1"),
+                record(b"b,c", Some(2), b"x\"y"),
+                record(b"d", None, b""),
+            ],
+            records.into_iter().map(Result::unwrap).collect::<Vec<_>>()
+        );
+
+        let records = read("a\t3\tx,y\n", Format::Tsv, Codec::Utf8);
+        assert_eq!(record(b"a", Some(3), b"x,y"), *records[0].as_ref().unwrap());
+
+        let input = "{\"row\":\"ff00\",\"value\":\"61\"}\n\n{\"row\":\"01\",\"timestamp\":4,\"value\":\"\"}";
+        let records = read(input, Format::Jsonl, Codec::Hex);
+        assert_eq!(
+            vec![
+                record(b"\xff\x00", None, b"a"),
+                record(b"\x01", Some(4), b""),
+            ],
+            records.into_iter().map(Result::unwrap).collect::<Vec<_>>()
+        );
+
+        let mut reader =
+            RecordReader::new(&b"row,value\na,1\n"[..], Format::Csv, Codec::Utf8, true);
+        assert_eq!(record(b"a", None, b"1"), reader.next().unwrap().unwrap());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let records = read("a,1\nb\nc,x,1\nd,1\n", Format::Csv, Codec::Utf8);
+        assert!(records[0].is_ok());
+        assert!(matches!(
+            records[1],
+            Err(FormatError::Fields { line: 2, n: 1 })
+        ));
+        assert!(matches!(
+            records[2],
+            Err(FormatError::Timestamp { line: 3 })
+        ));
+        assert!(records[3].is_ok());
+
+        let records = read("61,zz\n", Format::Csv, Codec::Hex);
+        assert!(matches!(
+            records[0],
+            Err(FormatError::Decode {
+                line: 1,
+                field: "value"
+            })
+        ));
+
+        let records = read("{\"row\":\"a\"}\n", Format::Jsonl, Codec::Utf8);
+        assert!(matches!(records[0], Err(FormatError::Json { line: 1, .. })));
+    }
+
+    #[test]
+    fn test_write() {
+        let records = [record(b"a", Some(1), b"x,y"), record(b"\xff", Some(2), b"")];
+        assert_eq!(
+            "row,timestamp,value\n61,1,782c79\nff,2,\n",
+            write(&records, Format::Csv, Codec::Hex, true)
+        );
+        assert_eq!(
+            "YQ==\t1\teCx5\n/w==\t2\t\n",
+            write(&records, Format::Tsv, Codec::Base64, false)
+        );
+        assert_eq!(
+            "{\"row\":\"a\",\"timestamp\":1,\"value\":\"x,y\"}\n",
+            write(&records[..1], Format::Jsonl, Codec::Utf8, false)
+        );
+
+        let mut out = vec![];
+        let mut writer = RecordWriter::new(&mut out, Format::Csv, Codec::Utf8, false).unwrap();
+        assert!(matches!(
+            writer.write(&records[1]),
+            Err(FormatError::Encode { field: "row", .. })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let records = vec![
+            record(b"a\"b", Some(1), b"x\ny"),
+            record(b"\x00\xff", Some(u64::max_value()), b"\t"),
+        ];
+        for &format in &[Format::Csv, Format::Tsv, Format::Jsonl] {
+            for &codec in &[Codec::Hex, Codec::Base64] {
+                let out = write(&records, format, codec, false);
+                let read = read(&out, format, codec)
+                    .into_iter()
+                    .map(Result::unwrap)
+                    .collect::<Vec<_>>();
+                assert_eq!(records, read);
+            }
+        }
+        let out = write(&records[..1], Format::Csv, Codec::Utf8, false);
+        let read = read(&out, Format::Csv, Codec::Utf8);
+        assert_eq!(records[0], *read[0].as_ref().unwrap());
+    }
+}
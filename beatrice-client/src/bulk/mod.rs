@@ -0,0 +1,217 @@
+mod format;
+
+pub use self::format::{Codec, Format, FormatError, Record, RecordReader, RecordWriter};
+use crate::client::{Batch, Client};
+use anyhow::Result;
+use bytes::Bytes;
+use std::{collections::VecDeque, io::Write, mem};
+use tokio::task::JoinHandle;
+
+/// Number of rows exported by each `Scan` RPC
+pub const EXPORT_PAGE_SIZE: u32 = 1000;
+
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// Number of records written by each `Batch` RPC
+    pub batch_size: usize,
+    /// Number of `Batch` RPCs in flight.
+    /// Records without timestamps are given the time their batch is applied,
+    /// so the latest of those of a row is the last in the input only if this is 1.
+    pub parallelism: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 1000,
+            parallelism: 4,
+        }
+    }
+}
+
+/// Writes `records` in batches and returns the number of the written records.
+/// `progress` is called with the number of the written records after each batch.
+/// Batches may be written out of order, so records of the same row and timestamp
+/// in different batches should be avoided.
+/// On an error, the batches in flight are still awaited, and the error tells the number
+/// of the written records.
+pub async fn import<I>(
+    client: &Client,
+    records: I,
+    options: &ImportOptions,
+    mut progress: impl FnMut(usize),
+) -> Result<usize>
+where
+    I: IntoIterator<Item = Result<Record, FormatError>>,
+{
+    let batch_size = options.batch_size.max(1);
+    let parallelism = options.parallelism.max(1);
+    let mut pending: VecDeque<JoinHandle<Result<usize>>> = VecDeque::new();
+    let mut written = 0;
+    let mut batch = Batch::new();
+
+    let mut records = records.into_iter();
+    let res: Result<()> = async {
+        loop {
+            let record = records.next().transpose()?;
+            let last = record.is_none();
+            if let Some(r) = record {
+                batch = match r.timestamp {
+                    Some(t) => batch.put_at(r.row, t, r.value),
+                    None => batch.put(r.row, r.value),
+                };
+            }
+            if batch.len() >= batch_size || (last && !batch.is_empty()) {
+                if pending.len() >= parallelism {
+                    written += pending.pop_front().unwrap().await??;
+                    progress(written);
+                }
+                let client = client.clone();
+                let batch = mem::take(&mut batch);
+                pending.push_back(tokio::spawn(async move {
+                    let n = batch.len();
+                    client.batch(batch).await?;
+                    Ok(n)
+                }));
+            }
+            if last {
+                break;
+            }
+        }
+        while let Some(handle) = pending.pop_front() {
+            written += handle.await??;
+            progress(written);
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = res {
+        for handle in pending {
+            if let Ok(Ok(n)) = handle.await {
+                written += n;
+                progress(written);
+            }
+        }
+        return Err(e.context(format!("{} records were written", written)));
+    }
+    Ok(written)
+}
+
+/// Rows exported by [`export`]
+#[derive(Clone, Debug, Default)]
+pub struct RowRange {
+    pub prefix: Bytes,
+    /// Inclusive
+    pub start: Option<Bytes>,
+    /// Exclusive
+    pub end: Option<Bytes>,
+}
+
+impl RowRange {
+    fn contains(&self, row: &[u8]) -> bool {
+        row.starts_with(&self.prefix)
+            && self.start.as_ref().map_or(true, |start| row >= &start[..])
+            && self.end.as_ref().map_or(true, |end| row < &end[..])
+    }
+
+    /// The longest prefix shared by all the rows in the range
+    fn scan_prefix(&self) -> Bytes {
+        match (&self.start, &self.end) {
+            (Some(start), Some(end)) if self.prefix.is_empty() => {
+                let n = start
+                    .iter()
+                    .zip(end.iter())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                start.slice(..n)
+            }
+            _ => self.prefix.clone(),
+        }
+    }
+}
+
+/// Writes the latest version of each row in `range` and returns the number of the written records.
+/// The rows are scanned in pages of [`EXPORT_PAGE_SIZE`] rows.
+pub async fn export<W>(
+    client: &Client,
+    range: &RowRange,
+    writer: &mut RecordWriter<W>,
+    mut progress: impl FnMut(usize),
+) -> Result<usize>
+where
+    W: Write,
+{
+    let prefix = range.scan_prefix();
+    let mut start = range.start.clone().unwrap_or_default();
+    let mut written = 0;
+    loop {
+        let entries = client
+            .scan_from(prefix.clone(), start, EXPORT_PAGE_SIZE)
+            .await?;
+        let last = entries.len() < EXPORT_PAGE_SIZE as usize;
+        start = match entries.last() {
+            Some(e) => next_row(&e.row),
+            None => break,
+        };
+        for e in entries.into_iter().filter(|e| range.contains(&e.row)) {
+            writer.write(&Record {
+                row: e.row,
+                timestamp: Some(e.timestamp),
+                value: e.value,
+            })?;
+            written += 1;
+            if written % 1000 == 0 {
+                progress(written);
+            }
+        }
+        let past_end = range.end.as_ref().map_or(false, |end| start >= *end);
+        if last || past_end {
+            break;
+        }
+    }
+    writer.flush()?;
+    progress(written);
+    Ok(written)
+}
+
+/// The smallest row greater than `row`
+fn next_row(row: &[u8]) -> Bytes {
+    let mut next = row.to_vec();
+    next.push(0);
+    Bytes::from(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range() {
+        let range = RowRange {
+            start: Some(Bytes::from("abc")),
+            end: Some(Bytes::from("abx")),
+            ..RowRange::default()
+        };
+        assert_eq!(Bytes::from("ab"), range.scan_prefix());
+        assert!(!range.contains(b"ab"));
+        assert!(range.contains(b"abc"));
+        assert!(range.contains(b"abw"));
+        assert!(!range.contains(b"abx"));
+
+        let range = RowRange {
+            prefix: Bytes::from("a"),
+            end: Some(Bytes::from("b")),
+            ..RowRange::default()
+        };
+        assert_eq!(Bytes::from("a"), range.scan_prefix());
+        assert!(range.contains(b"a"));
+        assert!(!range.contains(b"b"));
+
+        assert_eq!(Bytes::new(), RowRange::default().scan_prefix());
+        assert!(RowRange::default().contains(b""));
+
+        assert_eq!(Bytes::from("ab\0"), next_row(b"ab"));
+        assert!(range.contains(&next_row(b"a")));
+    }
+}
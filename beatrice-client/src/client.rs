@@ -0,0 +1,507 @@
+use beatrice_proto::beatrice::{
+    beatrice_client::BeatriceClient, mutation, BatchRequest, DeleteRangeRequest, DeleteRequest,
+    GetRequest, HistoryRequest, Mutation, PutRequest, ScanRequest,
+};
+use bytes::Bytes;
+use repc_client::configuration::Configuration as RepcConfiguration;
+use std::{
+    cmp, fmt,
+    future::Future,
+    time::{Duration, Instant},
+};
+use tonic::{transport::Channel, Code, Status};
+
+#[derive(Clone, Debug)]
+pub struct ClientOptions {
+    /// Deadline of each call including retries. `None` waits forever.
+    pub timeout: Option<Duration>,
+    /// Number of retries after the cluster is unavailable, e.g. while a new leader is elected.
+    pub retries: usize,
+    /// Backoff before the first retry, doubled on each retry.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retries: 5,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl ClientOptions {
+    fn backoff(&self, retry: usize) -> Duration {
+        let factor = 1u32.checked_shl(retry as u32).unwrap_or(u32::max_value());
+        cmp::min(
+            self.initial_backoff
+                .checked_mul(factor)
+                .unwrap_or(self.max_backoff),
+            self.max_backoff,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The row does not exist or is deleted.
+    NotFound,
+    /// The request was rejected by the server.
+    InvalidArgument(String),
+    /// The call did not complete before its deadline.
+    DeadlineExceeded,
+    /// The cluster could not be reached even after retries.
+    Transport(Status),
+    /// The server failed to handle the request, or the call failed with an unknown outcome,
+    /// which is not retried since the request may have been applied.
+    Server(Status),
+}
+
+impl Error {
+    fn is_retryable(&self) -> bool {
+        matches!(self, Error::Transport(_))
+    }
+}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        match status.code() {
+            Code::NotFound => Error::NotFound,
+            Code::InvalidArgument => Error::InvalidArgument(status.message().to_string()),
+            Code::DeadlineExceeded => Error::DeadlineExceeded,
+            Code::Unavailable => Error::Transport(status),
+            _ => Error::Server(status),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound => write!(f, "not found"),
+            Error::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            Error::DeadlineExceeded => write!(f, "deadline exceeded"),
+            Error::Transport(status) => write!(f, "transport failure: {}", status.message()),
+            Error::Server(status) => write!(f, "server error: {}", status),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A version of a row
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub row: Bytes,
+    pub timestamp: u64,
+    pub value: Bytes,
+}
+
+/// A version of a row returned by [`Client::history`]. Deleted versions have no value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub timestamp: u64,
+    pub value: Option<Bytes>,
+}
+
+/// Mutations applied atomically in order by [`Client::batch`].
+/// Timestamps of `0` are set to the same current time by the server.
+#[derive(Clone, Debug, Default)]
+pub struct Batch {
+    mutations: Vec<Mutation>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(self, row: impl Into<Bytes>, value: impl Into<Bytes>) -> Self {
+        self.put_at(row, 0, value)
+    }
+
+    pub fn put_at(
+        mut self,
+        row: impl Into<Bytes>,
+        timestamp: u64,
+        value: impl Into<Bytes>,
+    ) -> Self {
+        self.mutations.push(Mutation {
+            mutation: Some(mutation::Mutation::Put(PutRequest {
+                row: row.into().to_vec(),
+                timestamp,
+                value: value.into().to_vec(),
+            })),
+        });
+        self
+    }
+
+    pub fn delete(self, row: impl Into<Bytes>) -> Self {
+        self.delete_at(row, 0)
+    }
+
+    pub fn delete_at(mut self, row: impl Into<Bytes>, timestamp: u64) -> Self {
+        self.mutations.push(Mutation {
+            mutation: Some(mutation::Mutation::Delete(DeleteRequest {
+                row: row.into().to_vec(),
+                timestamp,
+            })),
+        });
+        self
+    }
+
+    /// Deletes all the rows in `[start, end)`.
+    pub fn delete_range(
+        mut self,
+        start: impl Into<Bytes>,
+        end: impl Into<Bytes>,
+        timestamp: u64,
+    ) -> Self {
+        self.mutations.push(Mutation {
+            mutation: Some(mutation::Mutation::DeleteRange(DeleteRangeRequest {
+                start: start.into().to_vec(),
+                end: end.into().to_vec(),
+                timestamp,
+            })),
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.mutations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mutations.is_empty()
+    }
+}
+
+/// A client of Beatrice which retries calls while the cluster is unavailable.
+#[derive(Clone)]
+pub struct Client {
+    inner: BeatriceClient<Channel>,
+    options: ClientOptions,
+}
+
+impl Client {
+    pub fn new(inner: BeatriceClient<Channel>) -> Self {
+        Self {
+            inner,
+            options: ClientOptions::default(),
+        }
+    }
+
+    pub fn from_conf(conf: RepcConfiguration) -> anyhow::Result<Self> {
+        Ok(Self::new(BeatriceClient::from_conf(conf)?))
+    }
+
+    pub fn with_options(mut self, options: ClientOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns a client sharing the connection whose calls fail after `timeout`.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        let mut client = self.clone();
+        client.options.timeout = Some(timeout);
+        client
+    }
+
+    pub fn options(&self) -> &ClientOptions {
+        &self.options
+    }
+
+    /// Writes `value` to `row` at the current time of the server.
+    pub async fn put(&self, row: impl Into<Bytes>, value: impl Into<Bytes>) -> Result<(), Error> {
+        self.put_at(row, 0, value).await
+    }
+
+    pub async fn put_at(
+        &self,
+        row: impl Into<Bytes>,
+        timestamp: u64,
+        value: impl Into<Bytes>,
+    ) -> Result<(), Error> {
+        let req = PutRequest {
+            row: row.into().to_vec(),
+            timestamp,
+            value: value.into().to_vec(),
+        };
+        self.call(|mut c| {
+            let req = req.clone();
+            async move { c.put(req).await }
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the latest version of `row`.
+    pub async fn get(&self, row: impl Into<Bytes>) -> Result<Entry, Error> {
+        self.get_at(row, u64::max_value()).await
+    }
+
+    /// Returns the latest version of `row` written at or before `timestamp`.
+    pub async fn get_at(&self, row: impl Into<Bytes>, timestamp: u64) -> Result<Entry, Error> {
+        let row = row.into();
+        let req = GetRequest {
+            row: row.to_vec(),
+            timestamp,
+        };
+        let res = self
+            .call(|mut c| {
+                let req = req.clone();
+                async move { c.get(req).await }
+            })
+            .await?;
+        Ok(Entry {
+            row,
+            timestamp: res.timestamp,
+            value: Bytes::from(res.value),
+        })
+    }
+
+    /// Deletes `row` at the current time of the server.
+    pub async fn delete(&self, row: impl Into<Bytes>) -> Result<(), Error> {
+        self.delete_at(row, 0).await
+    }
+
+    pub async fn delete_at(&self, row: impl Into<Bytes>, timestamp: u64) -> Result<(), Error> {
+        let req = DeleteRequest {
+            row: row.into().to_vec(),
+            timestamp,
+        };
+        self.call(|mut c| {
+            let req = req.clone();
+            async move { c.delete(req).await }
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the latest version of each row starting with `prefix` in order.
+    pub async fn scan(&self, prefix: impl Into<Bytes>) -> Result<Vec<Entry>, Error> {
+        self.scan_from(prefix, Bytes::new(), 0).await
+    }
+
+    /// Returns the latest version of up to `limit` rows starting with `prefix`
+    /// from `start` in order, or of all of them if `limit` is `0`.
+    /// The next page starts right after the last row returned.
+    pub async fn scan_from(
+        &self,
+        prefix: impl Into<Bytes>,
+        start: impl Into<Bytes>,
+        limit: u32,
+    ) -> Result<Vec<Entry>, Error> {
+        let req = ScanRequest {
+            prefix: prefix.into().to_vec(),
+            start: start.into().to_vec(),
+            limit,
+        };
+        let res = self
+            .call(|mut c| {
+                let req = req.clone();
+                async move { c.scan(req).await }
+            })
+            .await?;
+        Ok(res
+            .entries
+            .into_iter()
+            .map(|e| Entry {
+                row: Bytes::from(e.row),
+                timestamp: e.timestamp,
+                value: Bytes::from(e.value),
+            })
+            .collect())
+    }
+
+    /// Returns up to `limit` versions of `row` from the latest, or all of them if `limit` is `0`.
+    pub async fn history(&self, row: impl Into<Bytes>, limit: u32) -> Result<Vec<Version>, Error> {
+        let req = HistoryRequest {
+            row: row.into().to_vec(),
+            limit,
+        };
+        let res = self
+            .call(|mut c| {
+                let req = req.clone();
+                async move { c.history(req).await }
+            })
+            .await?;
+        Ok(res
+            .versions
+            .into_iter()
+            .map(|v| Version {
+                timestamp: v.timestamp,
+                value: if v.deleted {
+                    None
+                } else {
+                    Some(Bytes::from(v.value))
+                },
+            })
+            .collect())
+    }
+
+    pub async fn batch(&self, batch: Batch) -> Result<(), Error> {
+        let req = BatchRequest {
+            mutations: batch.mutations,
+        };
+        self.call(|mut c| {
+            let req = req.clone();
+            async move { c.batch(req).await }
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn call<T, F, Fut>(&self, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut(BeatriceClient<Channel>) -> Fut,
+        Fut: Future<Output = Result<tonic::Response<T>, Status>>,
+    {
+        let inner = &self.inner;
+        retry(&self.options, || f(inner.clone()))
+            .await
+            .map(|res| res.into_inner())
+    }
+}
+
+/// Calls `f` until it succeeds, fails with a non-retryable error, runs out of retries
+/// or exceeds the deadline.
+async fn retry<T, F, Fut>(options: &ClientOptions, mut f: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let deadline = options.timeout.map(|t| Instant::now() + t);
+    let mut retry = 0;
+    loop {
+        let res = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), f())
+                .await
+                .map_err(|_| Error::DeadlineExceeded)?,
+            None => f().await,
+        };
+        let err = match res {
+            Ok(res) => return Ok(res),
+            Err(status) => Error::from(status),
+        };
+        if !err.is_retryable() || retry >= options.retries {
+            return Err(err);
+        }
+
+        let backoff = options.backoff(retry);
+        if let Some(deadline) = deadline {
+            if Instant::now() + backoff >= deadline {
+                return Err(Error::DeadlineExceeded);
+            }
+        }
+        tracing::debug!(retry, error = %err, "retrying");
+        tokio::time::sleep(backoff).await;
+        retry += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn options() -> ClientOptions {
+        ClientOptions {
+            timeout: None,
+            retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+        }
+    }
+
+    #[test]
+    fn error() {
+        assert!(matches!(
+            Error::from(Status::not_found("not found")),
+            Error::NotFound
+        ));
+        assert!(matches!(
+            Error::from(Status::unavailable("no leader")),
+            Error::Transport(_)
+        ));
+        assert!(matches!(
+            Error::from(Status::internal("store is poisoned")),
+            Error::Server(_)
+        ));
+        // a write with a timestamp of 0 may have been applied before the connection broke
+        assert!(matches!(
+            Error::from(Status::unknown("connection reset")),
+            Error::Server(_)
+        ));
+    }
+
+    #[test]
+    fn backoff() {
+        let options = options();
+        let backoffs = (0..5).map(|i| options.backoff(i)).collect::<Vec<_>>();
+        assert_eq!(
+            vec![1, 2, 4, 4, 4],
+            backoffs
+                .iter()
+                .map(|d| d.as_millis() as u64)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Duration::from_millis(4), options.backoff(100));
+    }
+
+    #[tokio::test]
+    async fn retry_transport_failures() {
+        let calls = Cell::new(0);
+        let res = retry(&options(), || {
+            calls.set(calls.get() + 1);
+            let n = calls.get();
+            async move {
+                if n < 3 {
+                    Err(Status::unavailable("no leader"))
+                } else {
+                    Ok(n)
+                }
+            }
+        })
+        .await;
+        assert_eq!(3, res.unwrap());
+
+        calls.set(0);
+        let res: Result<(), _> = retry(&options(), || {
+            calls.set(calls.get() + 1);
+            async { Err(Status::unavailable("no leader")) }
+        })
+        .await;
+        assert!(matches!(res, Err(Error::Transport(_))));
+        assert_eq!(4, calls.get());
+    }
+
+    #[tokio::test]
+    async fn retry_not_found() {
+        let calls = Cell::new(0);
+        let res: Result<(), _> = retry(&options(), || {
+            calls.set(calls.get() + 1);
+            async { Err(Status::not_found("not found")) }
+        })
+        .await;
+        assert!(matches!(res, Err(Error::NotFound)));
+        assert_eq!(1, calls.get());
+    }
+
+    #[tokio::test]
+    async fn deadline() {
+        let options = ClientOptions {
+            timeout: Some(Duration::from_millis(20)),
+            ..options()
+        };
+        let res: Result<(), _> = retry(&options, || async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        })
+        .await;
+        assert!(matches!(res, Err(Error::DeadlineExceeded)));
+    }
+}
@@ -1,7 +1,23 @@
+use beatrice_proto::configuration;
 use repc_client::configuration::Configuration as RepcConfiguration;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Configuration {
     pub repc: RepcConfiguration,
+    /// Endpoint of the admin service, e.g. `http://127.0.0.1:50100`
+    #[serde(default)]
+    pub admin: Option<String>,
+}
+
+impl Configuration {
+    /// Loads the configuration from the YAML file, then overrides it with the `BEATRICE_*` variables in `env`
+    /// and finally with `overrides` such as `admin=http://127.0.0.1:50100`.
+    pub fn load<I>(file: Option<&Path>, env: I, overrides: &[String]) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        configuration::load(file, env, overrides)
+    }
 }
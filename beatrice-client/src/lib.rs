@@ -1,2 +1,6 @@
+pub mod bulk;
+mod client;
 pub mod configuration;
 pub mod shell;
+
+pub use self::client::{Batch, Client, ClientOptions, Entry, Error, Version};
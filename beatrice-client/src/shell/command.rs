@@ -1,3 +1,4 @@
+use super::{encoding::Encoding, timestamp::Timestamps};
 use bytes::Bytes;
 
 pub enum Command {
@@ -6,15 +7,48 @@ pub enum Command {
         timestamp: Option<u64>,
         value: Bytes,
     },
+    /// Reads the latest version at or before `timestamp` if given
     Get {
         row: Bytes,
+        timestamp: Option<u64>,
+    },
+    /// Lists up to `limit` versions from the latest
+    History {
+        row: Bytes,
+        limit: Option<u32>,
     },
     Delete {
         row: Bytes,
         timestamp: Option<u64>,
     },
+    DeleteRange {
+        start: Bytes,
+        end: Bytes,
+        timestamp: Option<u64>,
+    },
     Flush {
         cache: bool,
     },
+    Scan {
+        prefix: Bytes,
+    },
+    Stats,
+    SSTables,
+    Compact,
+    Dump {
+        row: Bytes,
+    },
+    /// Ingests the SSTable file at `path` into the store of every node
+    Ingest {
+        path: String,
+    },
+    /// Shows the output encoding if `encoding` is `None`
+    SetOutput {
+        encoding: Option<Encoding>,
+    },
+    /// Shows how timestamps are rendered if `timestamps` is `None`
+    SetTimestamps {
+        timestamps: Option<Timestamps>,
+    },
     Exit,
 }
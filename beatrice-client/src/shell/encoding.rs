@@ -0,0 +1,88 @@
+use std::{fmt, str};
+
+/// Encoding of rows and values printed by the shell.
+/// Hex and base64 are printed as literals accepted by the shell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    /// Prints invalid UTF-8 as hex.
+    Utf8,
+    Hex,
+    Base64,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Utf8
+    }
+}
+
+impl Encoding {
+    pub const NAMES: &'static [&'static str] = &["utf8", "hex", "base64"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "utf8" => Some(Encoding::Utf8),
+            "hex" => Some(Encoding::Hex),
+            "base64" => Some(Encoding::Base64),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self, v: &[u8]) -> String {
+        match self.encode_bare(v) {
+            (Encoding::Utf8, s) => s,
+            (Encoding::Hex, s) => format!("x\"{}\"", s),
+            (Encoding::Base64, s) => format!("b64\"{}\"", s),
+        }
+    }
+
+    /// Encodes `v` without the literal syntax of the shell.
+    /// Returns the encoding actually used, which is hex for invalid UTF-8.
+    pub fn encode_bare(&self, v: &[u8]) -> (Encoding, String) {
+        match self {
+            Encoding::Utf8 => match str::from_utf8(v) {
+                Ok(s) => (Encoding::Utf8, s.to_string()),
+                Err(_) => Encoding::Hex.encode_bare(v),
+            },
+            Encoding::Hex => {
+                let hex = v.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+                (Encoding::Hex, hex)
+            }
+            Encoding::Base64 => (Encoding::Base64, base64::encode(v)),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "utf8"),
+            Encoding::Hex => write!(f, "hex"),
+            Encoding::Base64 => write!(f, "base64"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::tokenizer::tokenize;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!("ab", Encoding::Utf8.encode(b"ab"));
+        assert_eq!(r#"x"61ff""#, Encoding::Utf8.encode(b"a\xff"));
+        assert_eq!(r#"x"61ff""#, Encoding::Hex.encode(b"a\xff"));
+        assert_eq!(r#"b64"YWI=""#, Encoding::Base64.encode(b"ab"));
+        assert_eq!(
+            (Encoding::Hex, "61ff".to_string()),
+            Encoding::Utf8.encode_bare(b"a\xff")
+        );
+
+        // literals are read back by the shell
+        for encoding in &[Encoding::Hex, Encoding::Base64] {
+            let v = b"\x00a b\xff";
+            assert_eq!(vec![&v[..]], tokenize(&encoding.encode(v)).unwrap());
+        }
+    }
+}
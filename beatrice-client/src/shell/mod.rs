@@ -1,32 +1,49 @@
 mod command;
+mod encoding;
+mod output;
 mod parser;
 mod prompter;
+mod timestamp;
+mod tokenizer;
 
 use self::{
     command::Command,
+    encoding::Encoding,
+    output::{Field, Settings, Table},
     parser::Parser,
-    prompter::{InteractivePrompter, Prompter},
+    prompter::InteractivePrompter,
+    timestamp::Timestamps,
+};
+pub use self::{
+    output::OutputFormat,
+    prompter::{Prompter, ScriptPrompter},
 };
 use anyhow::Result;
+use beatrice_proto::admin::{
+    admin_client::AdminClient, DumpKeyRequest, GetStatsRequest, ListTablesRequest, TableInfo,
+    TriggerCompactionRequest, VersionKind,
+};
 use beatrice_proto::beatrice::{
-    beatrice_client::BeatriceClient, DeleteRequest, FlushRequest, GetRequest, PutRequest,
+    beatrice_client::BeatriceClient, DeleteRangeRequest, DeleteRequest, FlushRequest, GetRequest,
+    HistoryRequest, IngestRequest, PutRequest, ScanRequest,
 };
 use bytes::Bytes;
+use std::fs;
 use tonic::transport::Channel;
 
 pub struct Shell<P = InteractivePrompter> {
     client: BeatriceClient<Channel>,
+    admin: Option<AdminClient<Channel>>,
     prompter: P,
     parser: Parser,
+    settings: Settings,
+    format: OutputFormat,
+    failures: usize,
 }
 
 impl Shell {
-    pub fn new(client: BeatriceClient<Channel>) -> Self {
-        Self {
-            client,
-            prompter: InteractivePrompter::new(),
-            parser: Parser::new(),
-        }
+    pub fn new(client: BeatriceClient<Channel>, admin: Option<AdminClient<Channel>>) -> Self {
+        Self::with_prompter(client, admin, InteractivePrompter::new())
     }
 }
 
@@ -34,16 +51,36 @@ impl<P> Shell<P>
 where
     P: Prompter,
 {
-    pub async fn run(mut self) -> Result<()> {
+    pub fn with_prompter(
+        client: BeatriceClient<Channel>,
+        admin: Option<AdminClient<Channel>>,
+        prompter: P,
+    ) -> Self {
+        Self {
+            client,
+            admin,
+            prompter,
+            parser: Parser::new(),
+            settings: Settings::default(),
+            format: OutputFormat::default(),
+            failures: 0,
+        }
+    }
+
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Runs commands until the prompter is exhausted or `exit` is given.
+    /// Returns the number of the failed commands.
+    pub async fn run(mut self) -> Result<usize> {
         loop {
             let command = match self.prompter.prompt() {
                 Ok(Some(command)) => command,
-                Ok(None) => {
-                    println!("");
-                    break;
-                }
+                Ok(None) => break,
                 Err(e) => {
-                    println!("failed to read command from prompt: {}", e);
+                    self.error(format!("failed to read command from prompt: {}", e));
                     break;
                 }
             };
@@ -51,7 +88,7 @@ where
                 Ok(command) => command,
                 Err(e) => {
                     if !matches!(e, parser::ParseError::Empty) {
-                        println!("failed to run command: {}", e);
+                        self.error(format!("failed to run command: {}", e));
                     }
                     continue;
                 }
@@ -62,15 +99,42 @@ where
                     timestamp,
                     value,
                 } => self.put(row, timestamp, value).await,
-                Command::Get { row } => self.get(row).await,
+                Command::Get { row, timestamp } => self.get(row, timestamp).await,
+                Command::History { row, limit } => self.history(row, limit).await,
                 Command::Delete { row, timestamp } => self.delete(row, timestamp).await,
+                Command::DeleteRange {
+                    start,
+                    end,
+                    timestamp,
+                } => self.delete_range(start, end, timestamp).await,
                 Command::Flush { cache } => self.flush(cache).await,
+                Command::Scan { prefix } => self.scan(prefix).await,
+                Command::Stats => self.stats().await,
+                Command::SSTables => self.sstables().await,
+                Command::Compact => self.compact().await,
+                Command::Dump { row } => self.dump(row).await,
+                Command::Ingest { path } => self.ingest(path).await,
+                Command::SetOutput { encoding } => self.set_output(encoding),
+                Command::SetTimestamps { timestamps } => self.set_timestamps(timestamps),
                 Command::Exit => {
                     break;
                 }
             }
         }
-        Ok(())
+        Ok(self.failures)
+    }
+
+    fn error(&mut self, e: String) {
+        self.failures += 1;
+        let e = self.format.error(&e);
+        self.prompter.print_error(e);
+    }
+
+    fn print_table(&mut self, table: Table) {
+        let out = table.render(self.format, &self.settings);
+        if !out.is_empty() {
+            self.prompter.print_result(out);
+        }
     }
 
     async fn put(&mut self, row: Bytes, timestamp: Option<u64>, value: Bytes) {
@@ -80,7 +144,7 @@ where
             value: value.to_vec(),
         };
         if let Err(status) = self.client.put(req).await {
-            self.prompter.print_error(status.to_string());
+            self.error(status.to_string());
         }
     }
 
@@ -90,26 +154,280 @@ where
             timestamp: timestamp.unwrap_or(0),
         };
         if let Err(status) = self.client.delete(req).await {
-            self.prompter.print_error(status.to_string());
+            self.error(status.to_string());
         }
     }
 
-    async fn get(&mut self, row: Bytes) {
-        let req = GetRequest { row: row.to_vec() };
+    async fn delete_range(&mut self, start: Bytes, end: Bytes, timestamp: Option<u64>) {
+        let req = DeleteRangeRequest {
+            start: start.to_vec(),
+            end: end.to_vec(),
+            timestamp: timestamp.unwrap_or(0),
+        };
+        if let Err(status) = self.client.delete_range(req).await {
+            self.error(status.to_string());
+        }
+    }
+
+    async fn get(&mut self, row: Bytes, timestamp: Option<u64>) {
+        let req = GetRequest {
+            row: row.to_vec(),
+            timestamp: timestamp.unwrap_or(0),
+        };
 
         match self.client.get(req).await {
             Ok(res) => {
-                self.prompter.print_result(res.into_inner().value);
+                let res = res.into_inner();
+                let table = Table::new(&["row", "timestamp", "value"]);
+                let mut table = match self.settings.timestamps {
+                    Timestamps::Off => table.with_raw(|f| f[2].clone()),
+                    _ => table.with_raw(|f| format!("{}\t{}", f[1], f[2])),
+                };
+                table.push(vec![
+                    Field::Bytes(row.to_vec()),
+                    Field::Timestamp(res.timestamp),
+                    Field::Bytes(res.value),
+                ]);
+                self.print_table(table);
             }
             Err(status) => {
-                self.prompter.print_error(status.to_string());
+                self.error(status.to_string());
+            }
+        }
+    }
+
+    async fn history(&mut self, row: Bytes, limit: Option<u32>) {
+        let req = HistoryRequest {
+            row: row.to_vec(),
+            limit: limit.unwrap_or(0),
+        };
+        match self.client.history(req).await {
+            Ok(res) => {
+                let mut table = Table::new(&["timestamp", "value"]);
+                for v in res.into_inner().versions {
+                    let value = if v.deleted {
+                        Field::Text("<deleted>".to_string())
+                    } else {
+                        Field::Bytes(v.value)
+                    };
+                    table.push(vec![Field::Timestamp(v.timestamp), value]);
+                }
+                self.print_table(table);
+            }
+            Err(status) => {
+                self.error(status.to_string());
             }
         }
     }
 
     async fn flush(&mut self, cache: bool) {
         let req = FlushRequest { cache };
-        let res = self.client.flush(req).await;
-        println!("=> {:?}", res);
+        if let Err(status) = self.client.flush(req).await {
+            self.error(status.to_string());
+        }
+    }
+
+    async fn scan(&mut self, prefix: Bytes) {
+        let req = ScanRequest {
+            prefix: prefix.to_vec(),
+            ..ScanRequest::default()
+        };
+        match self.client.scan(req).await {
+            Ok(res) => {
+                let mut table = Table::new(&["row", "timestamp", "value"]);
+                for e in res.into_inner().entries {
+                    table.push(vec![
+                        Field::Bytes(e.row),
+                        Field::Timestamp(e.timestamp),
+                        Field::Bytes(e.value),
+                    ]);
+                }
+                self.print_table(table);
+            }
+            Err(status) => {
+                self.error(status.to_string());
+            }
+        }
+    }
+
+    fn set_output(&mut self, encoding: Option<Encoding>) {
+        match encoding {
+            Some(encoding) => self.settings.encoding = encoding,
+            None => {
+                let mut table = Table::new(&["output"]);
+                table.push(vec![Field::Text(self.settings.encoding.to_string())]);
+                self.print_table(table);
+            }
+        }
+    }
+
+    fn set_timestamps(&mut self, timestamps: Option<Timestamps>) {
+        match timestamps {
+            Some(timestamps) => self.settings.timestamps = timestamps,
+            None => {
+                let mut table = Table::new(&["timestamps"]);
+                table.push(vec![Field::Text(self.settings.timestamps.to_string())]);
+                self.print_table(table);
+            }
+        }
+    }
+
+    fn admin(&mut self) -> Option<&mut AdminClient<Channel>> {
+        if self.admin.is_none() {
+            self.error("admin endpoint is not configured".to_string());
+        }
+        self.admin.as_mut()
+    }
+
+    async fn stats(&mut self) {
+        let admin = match self.admin() {
+            Some(admin) => admin,
+            None => return,
+        };
+        match admin.get_stats(GetStatsRequest {}).await {
+            Ok(res) => {
+                let res = res.into_inner();
+                if let Some(m) = res.memtable {
+                    let mut table =
+                        Table::new(&["entries", "key_size", "value_size", "range_tombstones"])
+                            .with_raw(|f| {
+                                format!(
+                            "memtable: entries={} key_size={} value_size={} range_tombstones={}",
+                            f[0], f[1], f[2], f[3]
+                        )
+                            });
+                    table.push(vec![
+                        Field::Int(m.entries),
+                        Field::Int(m.key_size),
+                        Field::Int(m.value_size),
+                        Field::Int(m.range_tombstones),
+                    ]);
+                    self.print_table(table);
+                }
+                self.print_table(tables(res.tables));
+            }
+            Err(status) => {
+                self.error(status.to_string());
+            }
+        }
+    }
+
+    async fn sstables(&mut self) {
+        let admin = match self.admin() {
+            Some(admin) => admin,
+            None => return,
+        };
+        match admin.list_ss_tables(ListTablesRequest {}).await {
+            Ok(res) => self.print_table(tables(res.into_inner().tables)),
+            Err(status) => {
+                self.error(status.to_string());
+            }
+        }
+    }
+
+    async fn compact(&mut self) {
+        let admin = match self.admin() {
+            Some(admin) => admin,
+            None => return,
+        };
+        if let Err(status) = admin.trigger_compaction(TriggerCompactionRequest {}).await {
+            self.error(status.to_string());
+        }
+    }
+
+    async fn ingest(&mut self, path: String) {
+        let file = match fs::read(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                self.error(format!("failed to read {}: {}", path, e));
+                return;
+            }
+        };
+        match self.client.ingest(IngestRequest { file }).await {
+            Ok(res) => {
+                let res = res.into_inner();
+                let columns = &["entries", "range_tombstones", "first_row", "last_row"];
+                let mut table = Table::new(columns).with_raw(|f| {
+                    format!(
+                        "ingested: entries={} range_tombstones={} rows={}..={}",
+                        f[0], f[1], f[2], f[3]
+                    )
+                });
+                table.push(vec![
+                    Field::Int(res.entries),
+                    Field::Int(res.range_tombstones),
+                    Field::Bytes(res.first_row),
+                    Field::Bytes(res.last_row),
+                ]);
+                self.print_table(table);
+            }
+            Err(status) => {
+                self.error(status.to_string());
+            }
+        }
+    }
+
+    async fn dump(&mut self, row: Bytes) {
+        let admin = match self.admin() {
+            Some(admin) => admin,
+            None => return,
+        };
+        let req = DumpKeyRequest { row: row.to_vec() };
+        match admin.dump_key(req).await {
+            Ok(res) => {
+                let mut table = Table::new(&["source", "timestamp", "value"]);
+                for v in res.into_inner().versions {
+                    let source = match v.table_id {
+                        0 => "memtable".to_string(),
+                        id => format!("sstable {}", id),
+                    };
+                    let value = match VersionKind::from_i32(v.kind) {
+                        Some(VersionKind::Value) => Field::Bytes(v.value),
+                        Some(VersionKind::Delete) => Field::Text("<deleted>".to_string()),
+                        Some(VersionKind::RangeDelete) => {
+                            Field::Text("<range deleted>".to_string())
+                        }
+                        None => Field::Text(format!("<unknown kind {}>", v.kind)),
+                    };
+                    table.push(vec![
+                        Field::Text(source),
+                        Field::Timestamp(v.timestamp),
+                        value,
+                    ]);
+                }
+                self.print_table(table);
+            }
+            Err(status) => {
+                self.error(status.to_string());
+            }
+        }
+    }
+}
+
+fn tables(tables: Vec<TableInfo>) -> Table {
+    let columns = &[
+        "id",
+        "entries",
+        "size",
+        "range_tombstones",
+        "first_row",
+        "last_row",
+    ];
+    let mut table = Table::new(columns).with_raw(|f| {
+        format!(
+            "sstable {}: entries={} size={} range_tombstones={} rows={}..={}",
+            f[0], f[1], f[2], f[3], f[4], f[5]
+        )
+    });
+    for t in tables {
+        table.push(vec![
+            Field::Int(t.id),
+            Field::Int(t.entries),
+            Field::Int(t.size),
+            Field::Int(t.range_tombstones),
+            Field::Bytes(t.first_row),
+            Field::Bytes(t.last_row),
+        ]);
     }
+    table
 }
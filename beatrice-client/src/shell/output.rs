@@ -0,0 +1,286 @@
+use super::{encoding::Encoding, timestamp::Timestamps};
+use std::str::FromStr;
+
+/// Format of the results printed by the shell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Plain text as typed into the shell
+    Raw,
+    /// A JSON object per line.
+    /// Bytes not printed as UTF-8 are followed by a `<column>_encoding` member naming the encoding.
+    Json,
+    /// An aligned table with a header
+    Table,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Raw
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(OutputFormat::Raw),
+            "json" => Ok(OutputFormat::Json),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!("unknown output format {}", s)),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn error(&self, e: &str) -> String {
+        match self {
+            OutputFormat::Json => format!("{{\"error\":{}}}", json_string(e)),
+            _ => format!("Error: {}", e),
+        }
+    }
+}
+
+/// How fields are rendered, changed by `set`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Settings {
+    pub encoding: Encoding,
+    pub timestamps: Timestamps,
+}
+
+pub enum Field {
+    Bytes(Vec<u8>),
+    Timestamp(u64),
+    Int(u64),
+    Text(String),
+}
+
+impl Field {
+    fn raw(&self, settings: &Settings) -> String {
+        match self {
+            Field::Timestamp(t) => format!("@{}", settings.timestamps.format(*t)),
+            f => f.text(settings),
+        }
+    }
+
+    fn text(&self, settings: &Settings) -> String {
+        match self {
+            Field::Bytes(v) => settings.encoding.encode(v),
+            Field::Timestamp(t) => settings.timestamps.format(*t),
+            Field::Int(n) => n.to_string(),
+            Field::Text(s) => s.clone(),
+        }
+    }
+
+    /// Renders the member of `column`. Timestamps are always numbers in JSON.
+    fn json(&self, column: &str, settings: &Settings) -> String {
+        let value = match self {
+            Field::Bytes(v) => match settings.encoding.encode_bare(v) {
+                (Encoding::Utf8, s) => json_string(&s),
+                (encoding, s) => {
+                    let name = format!("{}_encoding", column);
+                    format!(
+                        "{},{}:{}",
+                        json_string(&s),
+                        json_string(&name),
+                        json_string(&encoding.to_string())
+                    )
+                }
+            },
+            Field::Timestamp(t) | Field::Int(t) => t.to_string(),
+            f => json_string(&f.text(settings)),
+        };
+        format!("{}:{}", json_string(column), value)
+    }
+}
+
+/// Rows of a result with named columns.
+/// In the raw format, rows are printed with their fields separated by tabs unless `raw` is given.
+pub struct Table {
+    columns: &'static [&'static str],
+    rows: Vec<Vec<Field>>,
+    raw: Option<fn(&[String]) -> String>,
+}
+
+impl Table {
+    pub fn new(columns: &'static [&'static str]) -> Self {
+        Self {
+            columns,
+            rows: vec![],
+            raw: None,
+        }
+    }
+
+    /// Renders a row in the raw format from its formatted fields.
+    pub fn with_raw(mut self, raw: fn(&[String]) -> String) -> Self {
+        self.raw = Some(raw);
+        self
+    }
+
+    pub fn push(&mut self, row: Vec<Field>) {
+        debug_assert_eq!(self.columns.len(), row.len());
+        self.rows.push(row);
+    }
+
+    pub fn render(&self, format: OutputFormat, settings: &Settings) -> String {
+        match format {
+            OutputFormat::Raw => self.render_raw(settings),
+            OutputFormat::Json => self.render_json(settings),
+            OutputFormat::Table => self.render_table(settings),
+        }
+    }
+
+    fn render_raw(&self, settings: &Settings) -> String {
+        let lines = self.rows.iter().map(|row| {
+            let fields = row.iter().map(|f| f.raw(settings)).collect::<Vec<_>>();
+            match self.raw {
+                Some(raw) => raw(&fields),
+                None => fields.join("\t"),
+            }
+        });
+        lines.collect::<Vec<_>>().join("\n")
+    }
+
+    fn render_json(&self, settings: &Settings) -> String {
+        let lines = self.rows.iter().map(|row| {
+            let fields = self
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(c, f)| f.json(c, settings))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", fields.join(","))
+        });
+        lines.collect::<Vec<_>>().join("\n")
+    }
+
+    fn render_table(&self, settings: &Settings) -> String {
+        let header = self.columns.iter().map(|c| c.to_string()).collect();
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|f| f.text(settings)).collect::<Vec<_>>());
+        let lines = Some(header).into_iter().chain(rows).collect::<Vec<_>>();
+
+        let mut widths = vec![0; self.columns.len()];
+        for line in lines.iter() {
+            for (w, f) in widths.iter_mut().zip(line) {
+                *w = (*w).max(f.chars().count());
+            }
+        }
+        let lines = lines.iter().map(|line| {
+            let fields = line
+                .iter()
+                .zip(widths.iter())
+                .map(|(f, &w)| format!("{:w$}", f, w = w))
+                .collect::<Vec<_>>();
+            fields.join("  ").trim_end().to_string()
+        });
+        lines.collect::<Vec<_>>().join("\n")
+    }
+}
+
+fn json_string(s: &str) -> String {
+    serde_json::to_string(s).expect("strings are serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(encoding: Encoding) -> Settings {
+        Settings {
+            encoding,
+            ..Settings::default()
+        }
+    }
+
+    fn table() -> Table {
+        let mut table = Table::new(&["row", "timestamp", "value"]);
+        table.push(vec![
+            Field::Bytes(b"a".to_vec()),
+            Field::Timestamp(10),
+            Field::Bytes(b"x\"y".to_vec()),
+        ]);
+        table.push(vec![
+            Field::Bytes(b"bcd".to_vec()),
+            Field::Timestamp(2),
+            Field::Bytes(b"\xff".to_vec()),
+        ]);
+        table
+    }
+
+    #[test]
+    fn test_raw() {
+        assert_eq!(
+            "a\t@10\tx\"y\nbcd\t@2\tx\"ff\"",
+            table().render(OutputFormat::Raw, &settings(Encoding::Utf8))
+        );
+        let table = table().with_raw(|f| f[2].clone());
+        assert_eq!(
+            "b64\"eCJ5\"\nb64\"/w==\"",
+            table.render(OutputFormat::Raw, &settings(Encoding::Base64))
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            concat!(
+                r#"{"row":"a","timestamp":10,"value":"x\"y"}"#,
+                "\n",
+                r#"{"row":"bcd","timestamp":2,"value":"ff","value_encoding":"hex"}"#,
+            ),
+            table().render(OutputFormat::Json, &settings(Encoding::Utf8))
+        );
+        assert_eq!(
+            concat!(
+                r#"{"row":"YQ==","row_encoding":"base64","timestamp":10,"#,
+                r#""value":"eCJ5","value_encoding":"base64"}"#,
+                "\n",
+                r#"{"row":"YmNk","row_encoding":"base64","timestamp":2,"#,
+                r#""value":"/w==","value_encoding":"base64"}"#,
+            ),
+            table().render(OutputFormat::Json, &settings(Encoding::Base64))
+        );
+        assert_eq!(
+            r#"{"error":"not \"found\""}"#,
+            OutputFormat::Json.error("not \"found\"")
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            "row  timestamp  value\na    10         x\"y\nbcd  2          x\"ff\"",
+            table().render(OutputFormat::Table, &settings(Encoding::Utf8))
+        );
+        assert_eq!(
+            "row  timestamp  value",
+            Table::new(&["row", "timestamp", "value"])
+                .render(OutputFormat::Table, &settings(Encoding::Utf8))
+        );
+    }
+
+    #[test]
+    fn test_dates() {
+        let settings = Settings {
+            timestamps: Timestamps::Date,
+            ..Settings::default()
+        };
+        let mut table = Table::new(&["timestamp", "value"]);
+        table.push(vec![Field::Timestamp(1617280496789), Field::Int(1)]);
+        assert_eq!(
+            "@2021-04-01T12:34:56.789Z\t1",
+            table.render(OutputFormat::Raw, &settings)
+        );
+        assert_eq!(
+            r#"{"timestamp":1617280496789,"value":1}"#,
+            table.render(OutputFormat::Json, &settings)
+        );
+        assert_eq!(
+            "timestamp                 value\n2021-04-01T12:34:56.789Z  1",
+            table.render(OutputFormat::Table, &settings)
+        );
+    }
+}
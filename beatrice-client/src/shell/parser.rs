@@ -1,47 +1,92 @@
-use super::command::Command;
+use super::{
+    command::Command,
+    encoding::Encoding,
+    timestamp::{Timestamp, Timestamps},
+    tokenizer::{tokenize, TokenizeError},
+};
 use bytes::Bytes;
-use std::{error, fmt};
+use std::{error, fmt, str};
 
 pub struct Parser {}
 
 impl Parser {
     const TOKEN_PUT: &'static str = "put";
     const TOKEN_GET: &'static str = "get";
+    const TOKEN_HISTORY: &'static str = "history";
     const TOKEN_DELETE: &'static str = "delete";
+    const TOKEN_DELETE_RANGE: &'static str = "delete_range";
     const TOKEN_FLUSH: &'static str = "flush";
+    const TOKEN_SCAN: &'static str = "scan";
+    const TOKEN_STATS: &'static str = "stats";
+    const TOKEN_SSTABLES: &'static str = "sstables";
+    const TOKEN_COMPACT: &'static str = "compact";
+    const TOKEN_DUMP: &'static str = "dump";
+    const TOKEN_INGEST: &'static str = "ingest";
+    const TOKEN_SET: &'static str = "set";
     const TOKEN_EXIT: &'static str = "exit";
 
+    /// Names of all the commands
+    pub const COMMANDS: &'static [&'static str] = &[
+        Self::TOKEN_PUT,
+        Self::TOKEN_GET,
+        Self::TOKEN_HISTORY,
+        Self::TOKEN_DELETE,
+        Self::TOKEN_DELETE_RANGE,
+        Self::TOKEN_FLUSH,
+        Self::TOKEN_SCAN,
+        Self::TOKEN_STATS,
+        Self::TOKEN_SSTABLES,
+        Self::TOKEN_COMPACT,
+        Self::TOKEN_DUMP,
+        Self::TOKEN_INGEST,
+        Self::TOKEN_SET,
+        Self::TOKEN_EXIT,
+    ];
+
     pub fn new() -> Self {
         Parser {}
     }
 
     pub fn parse(&self, command: String) -> Result<Command, ParseError> {
-        self.parse_tokens(command.split_ascii_whitespace())
+        let tokens = tokenize(&command).map_err(ParseError::Tokenize)?;
+        self.parse_tokens(tokens.into_iter())
     }
 
-    fn parse_tokens<'a>(
-        &self,
-        mut tokens: impl Iterator<Item = &'a str>,
-    ) -> Result<Command, ParseError> {
+    fn parse_tokens(&self, mut tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
         let cmd = tokens.next().ok_or_else(|| ParseError::Empty)?;
+        let cmd = str::from_utf8(&cmd)
+            .map_err(|_| ParseError::Unknown(String::from_utf8_lossy(&cmd).into_owned()))?;
 
         match cmd {
             Self::TOKEN_PUT => self.parse_put(tokens),
             Self::TOKEN_GET => self.parse_get(tokens),
+            Self::TOKEN_HISTORY => self.parse_history(tokens),
             Self::TOKEN_DELETE => self.parse_delete(tokens),
+            Self::TOKEN_DELETE_RANGE => self.parse_delete_range(tokens),
             Self::TOKEN_FLUSH => self.parse_flush(tokens),
+            Self::TOKEN_SCAN => self.parse_scan(tokens),
+            Self::TOKEN_STATS => self.parse_no_args(Self::TOKEN_STATS, tokens, Command::Stats),
+            Self::TOKEN_SSTABLES => {
+                self.parse_no_args(Self::TOKEN_SSTABLES, tokens, Command::SSTables)
+            }
+            Self::TOKEN_COMPACT => {
+                self.parse_no_args(Self::TOKEN_COMPACT, tokens, Command::Compact)
+            }
+            Self::TOKEN_DUMP => self.parse_dump(tokens),
+            Self::TOKEN_INGEST => self.parse_ingest(tokens),
+            Self::TOKEN_SET => self.parse_set(tokens),
             Self::TOKEN_EXIT => self.parse_exit(tokens),
             _ => Err(ParseError::Unknown(cmd.to_string())),
         }
     }
 
-    fn parse_put<'a>(&self, tokens: impl Iterator<Item = &'a str>) -> Result<Command, ParseError> {
+    fn parse_put(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
         let mut args = tokens.collect::<Vec<_>>();
         let n = args.len();
         match n {
             2 => {
-                let value = Bytes::from(args.pop().unwrap().to_string());
-                let row = Bytes::from(args.pop().unwrap().to_string());
+                let value = args.pop().unwrap();
+                let row = args.pop().unwrap();
                 Ok(Command::Put {
                     row,
                     timestamp: None,
@@ -49,17 +94,9 @@ impl Parser {
                 })
             }
             3 => {
-                let value = Bytes::from(args.pop().unwrap().to_string());
-                let timestamp =
-                    args.pop()
-                        .unwrap()
-                        .parse::<u64>()
-                        .map_err(|e| ParseError::ArgParseFailed {
-                            arg: "timestamp",
-                            pos: 2,
-                            e: e.into(),
-                        })?;
-                let row = Bytes::from(args.pop().unwrap().to_string());
+                let value = args.pop().unwrap();
+                let timestamp = parse_arg::<u64>(args.pop().unwrap(), "timestamp", 2)?;
+                let row = args.pop().unwrap();
                 Ok(Command::Put {
                     row,
                     timestamp: Some(timestamp),
@@ -74,46 +111,79 @@ impl Parser {
         }
     }
 
-    fn parse_get<'a>(&self, tokens: impl Iterator<Item = &'a str>) -> Result<Command, ParseError> {
+    fn parse_get(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
         let mut args = tokens.collect::<Vec<_>>();
         let n = args.len();
-        if n != 1 {
-            return Err(ParseError::WrongArgNum {
+        match n {
+            1 => {
+                let row = args.pop().unwrap();
+                Ok(Command::Get {
+                    row,
+                    timestamp: None,
+                })
+            }
+            2 => {
+                let timestamp = args.pop().unwrap();
+                if !timestamp.starts_with(b"@") {
+                    return Err(ParseError::ArgParseFailed {
+                        arg: "timestamp",
+                        pos: 2,
+                        e: "must start with @".into(),
+                    });
+                }
+                let timestamp = parse_arg::<Timestamp>(timestamp.slice(1..), "timestamp", 2)?;
+                let row = args.pop().unwrap();
+                Ok(Command::Get {
+                    row,
+                    timestamp: Some(timestamp.0),
+                })
+            }
+            _ => Err(ParseError::WrongArgNum {
                 cmd: Self::TOKEN_GET,
                 expected: 1,
                 actual: n,
-            });
+            }),
         }
-        let row = Bytes::from(args.pop().unwrap().to_string());
+    }
 
-        Ok(Command::Get { row })
+    fn parse_history(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
+        let mut args = tokens.collect::<Vec<_>>();
+        let n = args.len();
+        match n {
+            1 => {
+                let row = args.pop().unwrap();
+                Ok(Command::History { row, limit: None })
+            }
+            2 => {
+                let limit = parse_arg::<u32>(args.pop().unwrap(), "limit", 2)?;
+                let row = args.pop().unwrap();
+                Ok(Command::History {
+                    row,
+                    limit: Some(limit),
+                })
+            }
+            _ => Err(ParseError::WrongArgNum {
+                cmd: Self::TOKEN_HISTORY,
+                expected: 1,
+                actual: n,
+            }),
+        }
     }
 
-    fn parse_delete<'a>(
-        &self,
-        tokens: impl Iterator<Item = &'a str>,
-    ) -> Result<Command, ParseError> {
+    fn parse_delete(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
         let mut args = tokens.collect::<Vec<_>>();
         let n = args.len();
         match n {
             1 => {
-                let row = Bytes::from(args.pop().unwrap().to_string());
+                let row = args.pop().unwrap();
                 Ok(Command::Delete {
                     row,
                     timestamp: None,
                 })
             }
             2 => {
-                let timestamp =
-                    args.pop()
-                        .unwrap()
-                        .parse::<u64>()
-                        .map_err(|e| ParseError::ArgParseFailed {
-                            arg: "timestamp",
-                            pos: 2,
-                            e: e.into(),
-                        })?;
-                let row = Bytes::from(args.pop().unwrap().to_string());
+                let timestamp = parse_arg::<u64>(args.pop().unwrap(), "timestamp", 2)?;
+                let row = args.pop().unwrap();
                 Ok(Command::Delete {
                     row,
                     timestamp: Some(timestamp),
@@ -127,22 +197,47 @@ impl Parser {
         }
     }
 
-    fn parse_flush<'a>(
+    fn parse_delete_range(
         &self,
-        tokens: impl Iterator<Item = &'a str>,
+        tokens: impl Iterator<Item = Bytes>,
     ) -> Result<Command, ParseError> {
+        let mut args = tokens.collect::<Vec<_>>();
+        let n = args.len();
+        match n {
+            2 => {
+                let end = args.pop().unwrap();
+                let start = args.pop().unwrap();
+                Ok(Command::DeleteRange {
+                    start,
+                    end,
+                    timestamp: None,
+                })
+            }
+            3 => {
+                let timestamp = parse_arg::<u64>(args.pop().unwrap(), "timestamp", 3)?;
+                let end = args.pop().unwrap();
+                let start = args.pop().unwrap();
+                Ok(Command::DeleteRange {
+                    start,
+                    end,
+                    timestamp: Some(timestamp),
+                })
+            }
+            _ => Err(ParseError::WrongArgNum {
+                cmd: Self::TOKEN_DELETE_RANGE,
+                expected: 2,
+                actual: n,
+            }),
+        }
+    }
+
+    fn parse_flush(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
         let mut args = tokens.collect::<Vec<_>>();
         let n = args.len();
         match n {
             0 => Ok(Command::Flush { cache: true }),
             1 => {
-                let cache = args.pop().unwrap().parse::<bool>().map_err(|e| {
-                    ParseError::ArgParseFailed {
-                        arg: "cache",
-                        pos: 1,
-                        e: e.into(),
-                    }
-                })?;
+                let cache = parse_arg::<bool>(args.pop().unwrap(), "cache", 1)?;
                 Ok(Command::Flush { cache })
             }
             _ => Err(ParseError::WrongArgNum {
@@ -153,22 +248,148 @@ impl Parser {
         }
     }
 
-    fn parse_exit<'a>(&self, tokens: impl Iterator<Item = &'a str>) -> Result<Command, ParseError> {
+    fn parse_scan(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
+        let mut args = tokens.collect::<Vec<_>>();
+        let n = args.len();
+        match n {
+            0 => Ok(Command::Scan {
+                prefix: Bytes::new(),
+            }),
+            1 => {
+                let prefix = args.pop().unwrap();
+                Ok(Command::Scan { prefix })
+            }
+            _ => Err(ParseError::WrongArgNum {
+                cmd: Self::TOKEN_SCAN,
+                expected: 1,
+                actual: n,
+            }),
+        }
+    }
+
+    fn parse_dump(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
+        let mut args = tokens.collect::<Vec<_>>();
+        let n = args.len();
+        if n != 1 {
+            return Err(ParseError::WrongArgNum {
+                cmd: Self::TOKEN_DUMP,
+                expected: 1,
+                actual: n,
+            });
+        }
+        let row = args.pop().unwrap();
+
+        Ok(Command::Dump { row })
+    }
+
+    fn parse_ingest(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
+        let mut args = tokens.collect::<Vec<_>>();
+        let n = args.len();
+        if n != 1 {
+            return Err(ParseError::WrongArgNum {
+                cmd: Self::TOKEN_INGEST,
+                expected: 1,
+                actual: n,
+            });
+        }
+        let path = parse_arg::<String>(args.pop().unwrap(), "path", 1)?;
+
+        Ok(Command::Ingest { path })
+    }
+
+    fn parse_set(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
+        let args = tokens.collect::<Vec<_>>();
+        let n = args.len();
+        match args.first().map(|name| &name[..]) {
+            Some(b"output") if n == 1 => Ok(Command::SetOutput { encoding: None }),
+            Some(b"output") if n == 2 => {
+                let encoding = str::from_utf8(&args[1])
+                    .ok()
+                    .and_then(Encoding::from_name)
+                    .ok_or_else(|| ParseError::ArgParseFailed {
+                        arg: "output",
+                        pos: 2,
+                        e: format!("must be one of {}", Encoding::NAMES.join(", ")).into(),
+                    })?;
+                Ok(Command::SetOutput {
+                    encoding: Some(encoding),
+                })
+            }
+            Some(b"timestamps") if n == 1 => Ok(Command::SetTimestamps { timestamps: None }),
+            Some(b"timestamps") if n == 2 => {
+                let timestamps = str::from_utf8(&args[1])
+                    .ok()
+                    .and_then(Timestamps::from_name)
+                    .ok_or_else(|| ParseError::ArgParseFailed {
+                        arg: "timestamps",
+                        pos: 2,
+                        e: format!("must be one of {}", Timestamps::NAMES.join(", ")).into(),
+                    })?;
+                Ok(Command::SetTimestamps {
+                    timestamps: Some(timestamps),
+                })
+            }
+            Some(b"output") | Some(b"timestamps") => Err(ParseError::WrongArgNum {
+                cmd: Self::TOKEN_SET,
+                expected: 2,
+                actual: n,
+            }),
+            Some(name) => Err(ParseError::ArgParseFailed {
+                arg: "name",
+                pos: 1,
+                e: format!("unknown setting {}", String::from_utf8_lossy(name)).into(),
+            }),
+            None => Err(ParseError::WrongArgNum {
+                cmd: Self::TOKEN_SET,
+                expected: 2,
+                actual: n,
+            }),
+        }
+    }
+
+    fn parse_exit(&self, tokens: impl Iterator<Item = Bytes>) -> Result<Command, ParseError> {
+        self.parse_no_args(Self::TOKEN_EXIT, tokens, Command::Exit)
+    }
+
+    fn parse_no_args(
+        &self,
+        cmd: &'static str,
+        tokens: impl Iterator<Item = Bytes>,
+        command: Command,
+    ) -> Result<Command, ParseError> {
         let n = tokens.count();
         if n != 0 {
             return Err(ParseError::WrongArgNum {
-                cmd: Self::TOKEN_EXIT,
+                cmd,
                 expected: 0,
                 actual: n,
             });
         }
-        Ok(Command::Exit)
+        Ok(command)
     }
 }
 
+fn parse_arg<T>(token: Bytes, arg: &'static str, pos: usize) -> Result<T, ParseError>
+where
+    T: str::FromStr,
+    T::Err: error::Error + Send + Sync + 'static,
+{
+    let token = str::from_utf8(&token).map_err(|e| ParseError::ArgParseFailed {
+        arg,
+        pos,
+        e: e.into(),
+    })?;
+    token.parse::<T>().map_err(|e| ParseError::ArgParseFailed {
+        arg,
+        pos,
+        e: e.into(),
+    })
+}
+
 #[derive(Debug)]
 pub enum ParseError {
     Empty,
+    Tokenize(TokenizeError),
     Unknown(String),
     WrongArgNum {
         cmd: &'static str,
@@ -186,6 +407,7 @@ impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ParseError::Empty => write!(f, "no command is specified"),
+            ParseError::Tokenize(e) => write!(f, "{}", e),
             ParseError::Unknown(cmd) => write!(f, "unknown command: {}", cmd),
             ParseError::WrongArgNum {
                 cmd,
@@ -206,3 +428,115 @@ impl fmt::Display for ParseError {
 }
 
 impl error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(command: &str) -> Result<Command, ParseError> {
+        Parser::new().parse(command.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(matches!(
+            parse(r#"put "a b" 3 x"ff00""#),
+            Ok(Command::Put { row, timestamp: Some(3), value })
+                if row == "a b" && &value[..] == b"\xff\x00"
+        ));
+        assert!(matches!(
+            parse("put a x"),
+            Ok(Command::Put {
+                timestamp: None,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(r#"get "a"#),
+            Err(ParseError::Tokenize(TokenizeError::Unterminated('"')))
+        ));
+        assert!(matches!(
+            parse("delete a x"),
+            Err(ParseError::ArgParseFailed {
+                arg: "timestamp",
+                ..
+            })
+        ));
+        assert!(matches!(parse(r#"x"ff""#), Err(ParseError::Unknown(_))));
+    }
+
+    #[test]
+    fn test_parse_get() {
+        assert!(matches!(
+            parse("get a"),
+            Ok(Command::Get {
+                timestamp: None,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("get a @42"),
+            Ok(Command::Get {
+                timestamp: Some(42),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("get a @2021-04-01T12:34:56.789Z"),
+            Ok(Command::Get {
+                timestamp: Some(1617280496789),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("get a 42"),
+            Err(ParseError::ArgParseFailed {
+                arg: "timestamp",
+                ..
+            })
+        ));
+        assert!(parse("get a @yesterday").is_err());
+        assert!(matches!(
+            parse("history a"),
+            Ok(Command::History { limit: None, .. })
+        ));
+        assert!(matches!(
+            parse("history a 3"),
+            Ok(Command::History { limit: Some(3), .. })
+        ));
+        assert!(parse("history a -1").is_err());
+    }
+
+    #[test]
+    fn test_parse_ingest() {
+        assert!(matches!(
+            parse("ingest 'data/1 2.sst'"),
+            Ok(Command::Ingest { path }) if path == "data/1 2.sst"
+        ));
+        assert!(parse("ingest").is_err());
+    }
+
+    #[test]
+    fn test_parse_set() {
+        assert!(matches!(
+            parse("set output hex"),
+            Ok(Command::SetOutput {
+                encoding: Some(Encoding::Hex)
+            })
+        ));
+        assert!(matches!(
+            parse("set output"),
+            Ok(Command::SetOutput { encoding: None })
+        ));
+        assert!(parse("set output json").is_err());
+        assert!(matches!(
+            parse("set timestamps date"),
+            Ok(Command::SetTimestamps {
+                timestamps: Some(Timestamps::Date)
+            })
+        ));
+        assert!(parse("set timestamps hex").is_err());
+        assert!(parse("set input hex").is_err());
+        assert!(parse("set").is_err());
+    }
+}
@@ -1,37 +1,175 @@
+use super::parser::Parser;
 use anyhow::Result;
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Context, Editor, Helper,
+};
 use std::{
-    fmt,
-    io::{self, Write},
+    env, fmt,
+    io::BufRead,
+    path::{Path, PathBuf},
     str,
 };
 
 pub trait Prompter {
     fn prompt(&mut self) -> Result<Option<String>>;
     fn print_result<T: AsRef<[u8]> + fmt::Debug>(&mut self, res: T);
+    /// Prints an error formatted by the shell.
     fn print_error(&mut self, e: String);
 }
 
-pub struct InteractivePrompter {}
+/// Reads commands from the terminal with line editing, history and completion of commands.
+/// A line ending with `\\` continues to the next line.
+pub struct InteractivePrompter {
+    editor: Editor<CommandHelper>,
+    history: Option<PathBuf>,
+}
 
 impl InteractivePrompter {
     const PROMPT: &'static str = "> ";
+    const HISTORY_FILE: &'static str = ".beatrice_history";
 
     pub fn new() -> Self {
-        Self {}
+        let mut editor = Editor::new();
+        editor.set_helper(Some(CommandHelper));
+        let history = env::var_os("HOME").map(|home| Path::new(&home).join(Self::HISTORY_FILE));
+        if let Some(history) = &history {
+            // the history file does not exist on the first run
+            let _ = editor.load_history(history);
+        }
+        Self { editor, history }
     }
 }
 
 impl Prompter for InteractivePrompter {
     fn prompt(&mut self) -> Result<Option<String>> {
-        let mut command = String::new();
+        let command = match self.editor.readline(Self::PROMPT) {
+            Ok(command) => command,
+            // discard the line on Ctrl-C
+            Err(ReadlineError::Interrupted) => return Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => {
+                // end the line of the prompt
+                println!();
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if !command.trim().is_empty() && self.editor.add_history_entry(command.as_str()) {
+            if let Some(history) = &self.history {
+                if let Err(e) = self.editor.save_history(history) {
+                    tracing::warn!(error = %e, "failed to save history");
+                }
+            }
+        }
+        Ok(Some(join_lines(&command)))
+    }
 
-        print!("{}", Self::PROMPT);
-        io::stdout().flush()?;
+    fn print_result<T: AsRef<[u8]> + fmt::Debug>(&mut self, v: T) {
+        let s = str::from_utf8(v.as_ref());
+        match s {
+            Ok(s) => println!("{}", s),
+            Err(_) => println!("{:?}", v),
+        }
+    }
 
-        let n = io::stdin().read_line(&mut command)?;
-        match n {
-            0 => Ok(None),
-            _ => Ok(Some(command)),
+    fn print_error(&mut self, e: String) {
+        println!("{}", e);
+    }
+}
+
+struct CommandHelper;
+
+impl Completer for CommandHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete_command(&line[..pos]))
+    }
+}
+
+impl Hinter for CommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CommandHelper {}
+
+impl Validator for CommandHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if continues(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Helper for CommandHelper {}
+
+/// Completes the command name at the start of `line`.
+fn complete_command(line: &str) -> (usize, Vec<String>) {
+    let start = line.len() - line.trim_start().len();
+    let word = &line[start..];
+    if word.contains(char::is_whitespace) {
+        return (start, vec![]);
+    }
+    let candidates = Parser::COMMANDS
+        .iter()
+        .filter(|c| c.starts_with(word))
+        .map(|c| c.to_string())
+        .collect();
+    (start, candidates)
+}
+
+fn continues(line: &str) -> bool {
+    line.trim_end_matches(&['\r', '\n'][..]).ends_with('\\')
+}
+
+/// Joins lines continued by trailing backslashes.
+fn join_lines(command: &str) -> String {
+    command.replace("\\\r\n", " ").replace("\\\n", " ")
+}
+
+/// Reads commands from `reader` without prompts, e.g. from a script file.
+/// Blank lines and lines starting with `#` are skipped.
+/// A line ending with `\\` continues to the next line.
+pub struct ScriptPrompter<R> {
+    reader: R,
+}
+
+impl<R> ScriptPrompter<R>
+where
+    R: BufRead,
+{
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R> Prompter for ScriptPrompter<R>
+where
+    R: BufRead,
+{
+    fn prompt(&mut self) -> Result<Option<String>> {
+        loop {
+            let mut command = String::new();
+            if self.reader.read_line(&mut command)? == 0 {
+                return Ok(None);
+            }
+            let trimmed = command.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            while continues(&command) && self.reader.read_line(&mut command)? > 0 {}
+            return Ok(Some(join_lines(&command)));
         }
     }
 
@@ -44,6 +182,55 @@ impl Prompter for InteractivePrompter {
     }
 
     fn print_error(&mut self, e: String) {
-        println!("Error: {}", e);
+        eprintln!("{}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script() {
+        let script = "put a 1\n\n# comment\n  get a\nscan";
+        let mut prompter = ScriptPrompter::new(script.as_bytes());
+        assert_eq!(Some("put a 1\n".to_string()), prompter.prompt().unwrap());
+        assert_eq!(Some("  get a\n".to_string()), prompter.prompt().unwrap());
+        assert_eq!(Some("scan".to_string()), prompter.prompt().unwrap());
+        assert_eq!(None, prompter.prompt().unwrap());
+
+        let script = "put a \\\n  1\nget a \\";
+        let mut prompter = ScriptPrompter::new(script.as_bytes());
+        assert_eq!(Some("put a    1\n".to_string()), prompter.prompt().unwrap());
+        assert_eq!(Some("get a \\".to_string()), prompter.prompt().unwrap());
+    }
+
+    #[test]
+    fn complete() {
+        assert_eq!(
+            (
+                0,
+                vec![
+                    "delete".to_string(),
+                    "delete_range".to_string(),
+                    "dump".to_string()
+                ]
+            ),
+            complete_command("d")
+        );
+        assert_eq!(
+            (
+                2,
+                vec![
+                    "scan".to_string(),
+                    "stats".to_string(),
+                    "sstables".to_string(),
+                    "set".to_string()
+                ]
+            ),
+            complete_command("  s")
+        );
+        assert_eq!((0, vec![]), complete_command("get a"));
+        assert_eq!(Parser::COMMANDS.len(), complete_command("").1.len());
     }
 }
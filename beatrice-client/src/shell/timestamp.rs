@@ -0,0 +1,215 @@
+use std::{error, fmt, str::FromStr};
+
+/// How the shell renders timestamps
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timestamps {
+    /// Milliseconds, hidden from the raw output of `get`
+    Off,
+    /// Milliseconds since the Unix epoch
+    Millis,
+    /// RFC 3339 dates in UTC
+    Date,
+}
+
+impl Timestamps {
+    pub const NAMES: &'static [&'static str] = &["off", "millis", "date"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Timestamps::Off),
+            "millis" => Some(Timestamps::Millis),
+            "date" => Some(Timestamps::Date),
+            _ => None,
+        }
+    }
+
+    pub fn format(&self, millis: u64) -> String {
+        match self {
+            Timestamps::Date => format_date(millis),
+            _ => millis.to_string(),
+        }
+    }
+}
+
+impl Default for Timestamps {
+    fn default() -> Self {
+        Timestamps::Off
+    }
+}
+
+impl fmt::Display for Timestamps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Timestamps::Off => "off",
+            Timestamps::Millis => "millis",
+            Timestamps::Date => "date",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Formats milliseconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SS.sssZ`.
+pub fn format_date(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        millis % 1000
+    )
+}
+
+/// A timestamp given to the shell, either milliseconds since the Unix epoch
+/// or an RFC 3339 date in UTC such as `2021-04-01T12:00:00Z`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timestamp(pub u64);
+
+impl FromStr for Timestamp {
+    type Err = TimestampError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(millis) = s.parse() {
+            return Ok(Timestamp(millis));
+        }
+        parse_date(s).map(Timestamp).ok_or(TimestampError)
+    }
+}
+
+#[derive(Debug)]
+pub struct TimestampError;
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "timestamp must be milliseconds or a date like 2021-04-01T12:00:00Z"
+        )
+    }
+}
+
+impl error::Error for TimestampError {}
+
+fn parse_date(s: &str) -> Option<u64> {
+    let s = s.strip_suffix('Z').or_else(|| s.strip_suffix('z'))?;
+    let (date, time) = s.split_at(s.find(|c| c == 'T' || c == 't')?);
+    let time = &time[1..];
+
+    let mut date = date.splitn(3, '-');
+    let year = parse_digits(date.next()?, 4)? as i64;
+    let month = parse_digits(date.next()?, 2)?;
+    let day = parse_digits(date.next()?, 2)?;
+
+    let (time, frac) = match time.find('.') {
+        Some(i) => (&time[..i], Some(&time[i + 1..])),
+        None => (time, None),
+    };
+    let mut time = time.splitn(3, ':');
+    let hour = parse_digits(time.next()?, 2)?;
+    let minute = parse_digits(time.next()?, 2)?;
+    let second = parse_digits(time.next()?, 2)?;
+    let millis = match frac {
+        // truncate to milliseconds, which slices at a char boundary only after the digits are checked
+        Some(frac) if (1..=9).contains(&frac.len()) && frac.bytes().all(|b| b.is_ascii_digit()) => {
+            let digits = &frac[..frac.len().min(3)];
+            parse_digits(digits, digits.len())? * 10u64.pow(3 - digits.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+
+    if year < 1970
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+    let days = days_from_civil(year, month, day) as u64;
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    Some(secs * 1000 + millis)
+}
+
+fn parse_digits(s: &str, len: usize) -> Option<u64> {
+    if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn days_in_month(year: i64, month: u64) -> u64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u64;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u64;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!("1970-01-01T00:00:00.000Z", format_date(0));
+        assert_eq!("2021-04-01T12:34:56.789Z", format_date(1617280496789));
+        assert_eq!("2000-02-29T23:59:59.999Z", format_date(951868799999));
+        assert_eq!("1617280496789", Timestamps::Millis.format(1617280496789));
+    }
+
+    #[test]
+    fn test_parse() {
+        let parse = |s: &str| s.parse::<Timestamp>().ok().map(|t| t.0);
+        assert_eq!(Some(42), parse("42"));
+        assert_eq!(Some(0), parse("1970-01-01T00:00:00Z"));
+        assert_eq!(Some(1617280496789), parse("2021-04-01T12:34:56.789Z"));
+        assert_eq!(Some(1617280496700), parse("2021-04-01t12:34:56.7z"));
+        assert_eq!(Some(951868799999), parse("2000-02-29T23:59:59.999999Z"));
+        for &ts in &[0, 1617280496789, 951868799999, 4102444800000] {
+            assert_eq!(Some(ts), parse(&format_date(ts)));
+        }
+
+        assert_eq!(None, parse("2021-04-01T12:34:56"));
+        assert_eq!(None, parse("2021-04-01T12:34:56+09:00"));
+        assert_eq!(None, parse("2021-02-29T00:00:00Z"));
+        assert_eq!(None, parse("2021-04-01T24:00:00Z"));
+        assert_eq!(None, parse("2021-04-01T12:34:56.Z"));
+        assert_eq!(None, parse("2021-04-01T12:34:56.7x9Z"));
+        assert_eq!(None, parse("2021-04-01T12:34:56.12éZ"));
+        assert_eq!(None, parse("2021-04-01T12:34:56.éZ"));
+        assert_eq!(None, parse("1969-12-31T23:59:59Z"));
+        assert_eq!(None, parse("-1"));
+    }
+}
@@ -0,0 +1,178 @@
+use bytes::Bytes;
+use std::{error, fmt};
+
+/// Splits `input` into tokens like a POSIX shell.
+///
+/// - `'...'` quotes characters literally.
+/// - `"..."` quotes characters except for backslash escapes.
+/// - `\n`, `\t`, `\r`, `\0` and `\xHH` are escape sequences. A backslash quotes any other character.
+/// - `x"..."` and `b64"..."` at the start of a token are hex and base64 literals.
+///
+/// Adjacent parts are concatenated into a token, e.g. `a"b c"` is `ab c`.
+pub fn tokenize(input: &str) -> Result<Vec<Bytes>, TokenizeError> {
+    let s = input.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    loop {
+        while i < s.len() && s[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == s.len() {
+            return Ok(tokens);
+        }
+
+        let mut token = vec![];
+        if s[i..].starts_with(b"x\"") {
+            let (literal, next) = literal(s, i + 2)?;
+            token.extend(decode_hex(literal)?);
+            i = next;
+        } else if s[i..].starts_with(b"b64\"") {
+            let (literal, next) = literal(s, i + 4)?;
+            token.extend(base64::decode(literal).map_err(TokenizeError::InvalidBase64)?);
+            i = next;
+        }
+        while i < s.len() && !s[i].is_ascii_whitespace() {
+            match s[i] {
+                b'\'' => {
+                    let end = find(s, i + 1, b'\'').ok_or(TokenizeError::Unterminated('\''))?;
+                    token.extend_from_slice(&s[i + 1..end]);
+                    i = end + 1;
+                }
+                b'"' => {
+                    i += 1;
+                    loop {
+                        match s.get(i) {
+                            None => return Err(TokenizeError::Unterminated('"')),
+                            Some(b'"') => break,
+                            Some(b'\\') => i = unescape(s, i + 1, &mut token)?,
+                            Some(&c) => {
+                                token.push(c);
+                                i += 1;
+                            }
+                        }
+                    }
+                    i += 1;
+                }
+                b'\\' => i = unescape(s, i + 1, &mut token)?,
+                c => {
+                    token.push(c);
+                    i += 1;
+                }
+            }
+        }
+        tokens.push(Bytes::from(token));
+    }
+}
+
+fn find(s: &[u8], start: usize, c: u8) -> Option<usize> {
+    s[start..].iter().position(|&b| b == c).map(|p| start + p)
+}
+
+/// Returns the body of the literal starting at `start` and the index after the closing quote.
+fn literal(s: &[u8], start: usize) -> Result<(&[u8], usize), TokenizeError> {
+    let end = find(s, start, b'"').ok_or(TokenizeError::Unterminated('"'))?;
+    Ok((&s[start..end], end + 1))
+}
+
+/// Unescapes the sequence following a backslash at `start - 1`
+/// and returns the index after the sequence.
+fn unescape(s: &[u8], start: usize, out: &mut Vec<u8>) -> Result<usize, TokenizeError> {
+    let c = *s.get(start).ok_or(TokenizeError::TrailingBackslash)?;
+    let b = match c {
+        b'n' => b'\n',
+        b't' => b'\t',
+        b'r' => b'\r',
+        b'0' => b'\0',
+        b'x' => {
+            let hex = s
+                .get(start + 1..start + 3)
+                .ok_or(TokenizeError::InvalidEscape)?;
+            let b = decode_hex(hex).map_err(|_| TokenizeError::InvalidEscape)?;
+            out.extend(b);
+            return Ok(start + 3);
+        }
+        c => c,
+    };
+    out.push(b);
+    Ok(start + 1)
+}
+
+fn decode_hex(hex: &[u8]) -> Result<Vec<u8>, TokenizeError> {
+    let digit = |c: u8| (c as char).to_digit(16).ok_or(TokenizeError::InvalidHex);
+    if hex.len() % 2 != 0 {
+        return Err(TokenizeError::InvalidHex);
+    }
+    hex.chunks(2)
+        .map(|d| Ok((digit(d[0])? * 16 + digit(d[1])?) as u8))
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TokenizeError {
+    Unterminated(char),
+    TrailingBackslash,
+    InvalidEscape,
+    InvalidHex,
+    InvalidBase64(base64::DecodeError),
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizeError::Unterminated(quote) => write!(f, "unterminated quote {}", quote),
+            TokenizeError::TrailingBackslash => write!(f, "trailing backslash"),
+            TokenizeError::InvalidEscape => write!(f, "\\x must be followed by 2 hex digits"),
+            TokenizeError::InvalidHex => write!(f, "hex literal must have pairs of hex digits"),
+            TokenizeError::InvalidBase64(e) => write!(f, "invalid base64 literal: {}", e),
+        }
+    }
+}
+
+impl error::Error for TokenizeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<Bytes> {
+        tokenize(input).unwrap()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(vec!["put", "a", "1"], tokens("  put a\t1 \n"));
+        assert!(tokens("").is_empty());
+        assert_eq!(vec!["a b", "c  d"], tokens(r#"'a b' "c  d""#));
+        assert_eq!(vec!["ab c"], tokens(r#"a"b c""#));
+        assert_eq!(vec![r#"a\"b"#], tokens(r#"'a\"b'"#));
+        assert_eq!(vec![r#"a"b'c"#], tokens(r#""a\"b'c""#));
+        assert_eq!(vec!["a b"], tokens(r"a\ b"));
+        assert_eq!(vec!["\n\t\0\x7f"], tokens(r#""\n\t\0\x7f""#));
+        assert_eq!(vec![""], tokens(r#""""#));
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(
+            vec![Bytes::from(&[0xde, 0xad, 0xbe, 0xef][..])],
+            tokens(r#"x"deadBEEF""#)
+        );
+        assert_eq!(vec![Bytes::from(&b"\xff\x00a"[..])], tokens(r#"x"ff00"a"#));
+        assert_eq!(vec![Bytes::from("hello")], tokens(r#"b64"aGVsbG8=""#));
+        // only at the start of a token
+        assert_eq!(vec!["ax00"], tokens(r#"ax"00""#));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Err(TokenizeError::Unterminated('"')), tokenize(r#"put "a"#));
+        assert_eq!(Err(TokenizeError::Unterminated('\'')), tokenize("'a"));
+        assert_eq!(Err(TokenizeError::TrailingBackslash), tokenize(r"a\"));
+        assert_eq!(Err(TokenizeError::InvalidEscape), tokenize(r"\xg0"));
+        assert_eq!(Err(TokenizeError::InvalidHex), tokenize(r#"x"abc""#));
+        assert!(matches!(
+            tokenize(r#"b64"a""#),
+            Err(TokenizeError::InvalidBase64(_))
+        ));
+    }
+}
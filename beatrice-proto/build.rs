@@ -1,4 +1,5 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     repc_build::compile_protos("proto/beatrice.proto")?;
+    tonic_build::compile_protos("proto/admin.proto")?;
     Ok(())
 }
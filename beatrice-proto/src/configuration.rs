@@ -0,0 +1,130 @@
+//! Loading of the configurations of the server and the client.
+
+use anyhow::{anyhow, Context};
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+use std::{fs::File, io::BufReader, path::Path};
+
+/// Prefix of the environment variables overriding the configuration.
+/// `__` separates the fields, e.g. `BEATRICE_STORAGE__MEMTABLE_ROWS=4096`.
+pub const ENV_PREFIX: &str = "BEATRICE_";
+
+/// Loads a configuration from the YAML file, then overrides it with the `BEATRICE_*` variables in `env`
+/// and finally with `overrides` such as `storage.memtable_rows=4096`.
+/// Values are parsed as YAML, so `compaction={full: {tables: 8}}` sets a mapping.
+pub fn load<T, I>(file: Option<&Path>, env: I, overrides: &[String]) -> anyhow::Result<T>
+where
+    T: DeserializeOwned,
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut value = match file {
+        Some(path) => {
+            let file =
+                File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+            serde_yaml::from_reader(BufReader::new(file))?
+        }
+        None => Value::Mapping(Mapping::new()),
+    };
+    let mut env: Vec<_> = env
+        .into_iter()
+        .filter_map(|(name, raw)| {
+            let path = name
+                .strip_prefix(ENV_PREFIX)?
+                .to_lowercase()
+                .replace("__", ".");
+            Some((path, raw))
+        })
+        .collect();
+    env.sort();
+    for (path, raw) in &env {
+        set(&mut value, path, raw);
+    }
+    for o in overrides {
+        let mut kv = o.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some(path), Some(raw)) if !path.is_empty() => set(&mut value, path, raw),
+            _ => return Err(anyhow!("override must be <field>=<value>: {}", o)),
+        }
+    }
+    Ok(serde_yaml::from_value(value)?)
+}
+
+/// Sets the field at the dotted `path` to `raw` parsed as YAML, creating missing mappings.
+fn set(value: &mut Value, path: &str, raw: &str) {
+    let field = path.split('.').fold(value, field);
+    *field = serde_yaml::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+}
+
+fn field<'a>(value: &'a mut Value, key: &str) -> &'a mut Value {
+    let index = key.parse::<usize>().ok();
+    let nested = match value {
+        Value::Sequence(seq) => index.map_or(false, |i| i < seq.len()),
+        Value::Mapping(_) => true,
+        _ => false,
+    };
+    if !nested {
+        *value = Value::Mapping(Mapping::new());
+    }
+    match value {
+        Value::Sequence(seq) => &mut seq[index.unwrap()],
+        Value::Mapping(map) => {
+            // keys may be numbers, e.g. node IDs
+            let key = map
+                .iter()
+                .map(|(k, _)| k)
+                .find(|k| match k {
+                    Value::String(s) => s == key,
+                    Value::Number(n) => n.to_string() == key,
+                    _ => false,
+                })
+                .cloned()
+                .unwrap_or_else(|| Value::String(key.to_string()));
+            if !map.contains_key(&key) {
+                map.insert(key.clone(), Value::Null);
+            }
+            map.get_mut(&key).unwrap()
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let mut value: Value =
+            serde_yaml::from_str("storage: {memtable_rows: 16}\nnodes: {1: [a, b]}").unwrap();
+        set(&mut value, "storage.memtable_rows", "4096");
+        set(&mut value, "storage.compaction", "{full: {tables: 8}}");
+        set(&mut value, "nodes.1.0", "c");
+        set(&mut value, "admin.addr", "127.0.0.1:50100");
+        let expected: Value = serde_yaml::from_str(
+            "storage: {memtable_rows: 4096, compaction: {full: {tables: 8}}}
+nodes: {1: [c, b]}
+admin: {addr: '127.0.0.1:50100'}",
+        )
+        .unwrap();
+        assert_eq!(expected, value);
+    }
+
+    #[test]
+    fn test_load() {
+        let env = vec![
+            (
+                "BEATRICE_STORAGE__MEMTABLE_ROWS".to_string(),
+                "16".to_string(),
+            ),
+            ("BEATRICE_ADMIN".to_string(), "a".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ];
+        // overrides win over the environment
+        let value: Value = load(None, env, &["admin=b".to_string()]).unwrap();
+        let expected: Value =
+            serde_yaml::from_str("{storage: {memtable_rows: 16}, admin: b}").unwrap();
+        assert_eq!(expected, value);
+
+        assert!(load::<Value, _>(None, vec![], &["admin".to_string()]).is_err());
+    }
+}
@@ -1,3 +1,9 @@
+pub mod configuration;
+
 pub mod beatrice {
     tonic::include_proto!("beatrice");
 }
+
+pub mod admin {
+    tonic::include_proto!("beatrice.admin");
+}
@@ -0,0 +1,75 @@
+use beatrice_server::{
+    model::Row,
+    sstable::{Filter, FilterBuilder, FilterKind, FilterSize},
+};
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const N: usize = 100_000;
+const SIZE: FilterSize = FilterSize::FalsePositiveRate(0.01);
+const KINDS: [FilterKind; 4] = [
+    FilterKind::Bloom,
+    FilterKind::BlockedBloom,
+    FilterKind::Cuckoo,
+    FilterKind::Xor,
+];
+
+fn rows(prefix: &str) -> Vec<Row> {
+    (0..N)
+        .map(|i| Row::new(Bytes::from(format!("{}{:08}", prefix, i))))
+        .collect()
+}
+
+fn build(kind: FilterKind, rows: &[Row]) -> Filter {
+    let mut builder = FilterBuilder::new(kind, SIZE);
+    for row in rows {
+        builder.insert(row.clone());
+    }
+    builder.build()
+}
+
+fn bench_contains(c: &mut Criterion) {
+    let present = rows("present");
+    let absent = rows("absent");
+
+    let mut group = c.benchmark_group("contains");
+    for &kind in KINDS.iter() {
+        let filter = build(kind, &present);
+
+        let positives = absent.iter().filter(|row| filter.contains(row)).count();
+        println!(
+            "{:?}: bits per key = {:.2}, false positive rate = {:.4} (estimated {:.4})",
+            kind,
+            filter.m() as f64 / N as f64,
+            positives as f64 / N as f64,
+            filter.false_positive_rate(N),
+        );
+
+        let id = format!("{:?}", kind);
+        group.bench_function(BenchmarkId::new("present", &id), |b| {
+            let mut rows = present.iter().cycle();
+            b.iter(|| filter.contains(rows.next().unwrap()))
+        });
+        group.bench_function(BenchmarkId::new("absent", &id), |b| {
+            let mut rows = absent.iter().cycle();
+            b.iter(|| filter.contains(rows.next().unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_build(c: &mut Criterion) {
+    let present = rows("present");
+
+    let mut group = c.benchmark_group("build");
+    group.sample_size(10);
+    for &kind in KINDS.iter() {
+        group.bench_function(BenchmarkId::from_parameter(format!("{:?}", kind)), |b| {
+            b.iter(|| build(kind, &present))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_contains, bench_build);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+use crate::{
+    model::{Row, Value},
+    store::{Store, TableInfo},
+};
+use beatrice_proto::admin::{
+    admin_server::Admin, DumpKeyRequest, DumpKeyResponse, GetStatsRequest, GetStatsResponse,
+    ListTablesRequest, ListTablesResponse, MemtableStats, TriggerCompactionRequest,
+    TriggerCompactionResponse, Version, VersionKind,
+};
+use bytes::Bytes;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tonic::{Request, Response, Status};
+
+/// A service to inspect and maintain the store of the local node.
+///
+/// Requests are not replicated; they are served by the node that receives them.
+pub struct AdminService {
+    store: Arc<Mutex<Store>>,
+}
+
+impl AdminService {
+    pub(crate) fn new(store: Arc<Mutex<Store>>) -> Self {
+        Self { store }
+    }
+
+    fn store(&self) -> Result<MutexGuard<Store>, Status> {
+        self.store
+            .lock()
+            .map_err(|_| Status::internal("store is poisoned"))
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn get_stats(
+        &self,
+        _req: Request<GetStatsRequest>,
+    ) -> Result<Response<GetStatsResponse>, Status> {
+        let store = self.store()?;
+        let (stat, tombstones) = store.memtable_stat();
+        let memtable = MemtableStats {
+            entries: stat.len() as u64,
+            key_size: stat.key_size() as u64,
+            value_size: stat.value_size() as u64,
+            range_tombstones: tombstones as u64,
+        };
+        let tables = store.tables().into_iter().map(to_table_info).collect();
+
+        Ok(Response::new(GetStatsResponse {
+            memtable: Some(memtable),
+            tables,
+        }))
+    }
+
+    async fn list_ss_tables(
+        &self,
+        _req: Request<ListTablesRequest>,
+    ) -> Result<Response<ListTablesResponse>, Status> {
+        let tables = self
+            .store()?
+            .tables()
+            .into_iter()
+            .map(to_table_info)
+            .collect();
+        Ok(Response::new(ListTablesResponse { tables }))
+    }
+
+    /// Merges the tables of this node, which never changes what reads return.
+    /// The memtable is not flushed, since a version in the memtable wins over one with the same key
+    /// in the tables, and nodes thus have to flush at the same point of the log.
+    async fn trigger_compaction(
+        &self,
+        _req: Request<TriggerCompactionRequest>,
+    ) -> Result<Response<TriggerCompactionResponse>, Status> {
+        self.store()?.compact();
+        Ok(Response::new(TriggerCompactionResponse {}))
+    }
+
+    async fn dump_key(
+        &self,
+        req: Request<DumpKeyRequest>,
+    ) -> Result<Response<DumpKeyResponse>, Status> {
+        let row = Row::new(Bytes::from(req.into_inner().row));
+        let versions = self
+            .store()?
+            .dump(&row)
+            .into_iter()
+            .map(|v| {
+                let (kind, value) = match v.value {
+                    Some(Value::Val(value)) => (VersionKind::Value, value.to_vec()),
+                    Some(Value::Del) => (VersionKind::Delete, vec![]),
+                    None => (VersionKind::RangeDelete, vec![]),
+                };
+                Version {
+                    table_id: v.table_id.unwrap_or(0),
+                    timestamp: v.timestamp,
+                    kind: kind as i32,
+                    value,
+                }
+            })
+            .collect();
+        Ok(Response::new(DumpKeyResponse { versions }))
+    }
+}
+
+fn to_table_info(t: TableInfo) -> beatrice_proto::admin::TableInfo {
+    beatrice_proto::admin::TableInfo {
+        id: t.id,
+        entries: t.len as u64,
+        size: t.size as u64,
+        range_tombstones: t.tombstones as u64,
+        first_row: t.first_row.map_or_else(Vec::new, |r| r.get().to_vec()),
+        last_row: t.last_row.map_or_else(Vec::new, |r| r.get().to_vec()),
+    }
+}
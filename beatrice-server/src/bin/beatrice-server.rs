@@ -1,16 +1,26 @@
 use anyhow::Result;
-use beatrice_server::{configuration::Configuration, BeatriceState, BeatriceStateMachine};
+use beatrice_proto::admin::admin_server::AdminServer;
+use beatrice_server::{configuration::Configuration, metrics, BeatriceState, BeatriceStateMachine};
 use clap::{AppSettings, Clap};
 use repc::group::grpc::GrpcRepcGroup;
-use std::{fs::File, io::BufReader, path::Path};
+use std::{env, path::Path};
+use tonic::transport::Server;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{fmt::format::DefaultFields, EnvFilter};
 
 #[derive(Clap)]
 #[clap(setting = AppSettings::ColoredHelp)]
 struct Opts {
+    /// YAML file of the configuration
     #[clap(long)]
-    conf: String,
+    conf: Option<String>,
+    /// Overrides a field of the configuration, e.g. `--set storage.memtable_rows=4096`.
+    /// Takes precedence over the file and `BEATRICE_*` environment variables
+    #[clap(long, number_of_values = 1)]
+    set: Vec<String>,
+    /// Prints the effective configuration and exits
+    #[clap(long)]
+    print_config: bool,
     id: u32,
 }
 
@@ -22,11 +32,8 @@ fn init_tracing_subscriber() {
         .try_init();
 }
 
-fn load_conf<P: AsRef<Path>>(path: P) -> Result<Configuration> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let conf = serde_yaml::from_reader(reader)?;
-    Ok(conf)
+fn load_conf(opts: &Opts) -> Result<Configuration> {
+    Configuration::load(opts.conf.as_deref().map(Path::new), env::vars(), &opts.set)
 }
 
 #[tokio::main]
@@ -36,21 +43,62 @@ async fn main() -> Result<()> {
     init_tracing_subscriber();
 
     let id = opts.id;
-    let conf = match load_conf(&opts.conf) {
+    let file = opts.conf.as_deref().unwrap_or_default();
+    let conf = match load_conf(&opts) {
         Ok(conf) => conf,
         Err(e) => {
             tracing::error!(
                 error = <String as AsRef<str>>::as_ref(&e.to_string()),
-                file = <String as AsRef<str>>::as_ref(&opts.conf),
-                "failed to load configuration from file",
+                file,
+                "failed to load configuration",
             );
             return Err(e);
         }
     };
+    if let Err(e) = conf.validate() {
+        tracing::error!(
+            error = <String as AsRef<str>>::as_ref(&e.to_string()),
+            file,
+            "invalid configuration",
+        );
+        return Err(e.into());
+    }
+    if opts.print_config {
+        print!("{}", serde_yaml::to_string(&conf)?);
+        return Ok(());
+    }
 
-    let Configuration { repc: conf } = conf;
+    let Configuration {
+        repc: conf,
+        metrics: metrics_conf,
+        admin: admin_conf,
+        storage: storage_conf,
+    } = conf;
 
-    let state = BeatriceState::new();
+    let state = BeatriceState::with_storage(&storage_conf);
+    if let Some(metrics_conf) = metrics_conf {
+        let metrics = state.metrics();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(metrics_conf.addr, metrics).await {
+                tracing::error!(
+                    error = <String as AsRef<str>>::as_ref(&e.to_string()),
+                    "failed to serve metrics",
+                );
+            }
+        });
+    }
+    if let Some(admin_conf) = admin_conf {
+        let admin = AdminServer::new(state.admin());
+        tokio::spawn(async move {
+            let server = Server::builder().add_service(admin);
+            if let Err(e) = server.serve(admin_conf.addr).await {
+                tracing::error!(
+                    error = <String as AsRef<str>>::as_ref(&e.to_string()),
+                    "failed to serve admin service",
+                );
+            }
+        });
+    }
     let state_machine = BeatriceStateMachine::new(state);
     let group = GrpcRepcGroup::new(id, conf, state_machine);
 
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use beatrice_server::{
+    model::{Key, Row, Value},
+    sstable::{
+        file::{self, BlockHandle},
+        FilterKind,
+    },
+};
+use bytes::Bytes;
+use clap::{AppSettings, Clap};
+use std::{ascii, fs};
+
+/// Prints the contents of an SSTable file.
+#[derive(Clap)]
+#[clap(setting = AppSettings::ColoredHelp)]
+struct Opts {
+    /// Path to the SSTable file
+    file: String,
+    /// Verify checksums of the blocks and the order of the keys
+    #[clap(long)]
+    verify: bool,
+    /// Print only entries whose row starts with this prefix
+    #[clap(long)]
+    prefix: Option<String>,
+    /// Print only entries with timestamps greater than or equal to this
+    #[clap(long)]
+    min_timestamp: Option<u64>,
+    /// Print only entries with timestamps less than or equal to this
+    #[clap(long)]
+    max_timestamp: Option<u64>,
+}
+
+impl Opts {
+    fn matches(&self, key: &Key) -> bool {
+        let prefix = self.prefix.as_ref().map_or(&b""[..], |p| p.as_bytes());
+        key.row().get().starts_with(prefix)
+            && self.min_timestamp.map_or(true, |t| t <= key.timestamp())
+            && self.max_timestamp.map_or(true, |t| key.timestamp() <= t)
+    }
+}
+
+fn escape(bytes: &Bytes) -> String {
+    let escaped = bytes
+        .iter()
+        .flat_map(|&b| ascii::escape_default(b))
+        .collect::<Vec<_>>();
+    String::from_utf8(escaped).unwrap()
+}
+
+fn print_handle(name: &str, handle: &BlockHandle) {
+    println!(
+        "  {}: offset={} len={} checksum={:#010x}",
+        name, handle.offset, handle.len, handle.checksum
+    );
+}
+
+fn row(row: &Row) -> String {
+    format!("\"{}\"", escape(row.get()))
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::parse();
+
+    let buf = Bytes::from(fs::read(&opts.file)?);
+    let size = buf.len();
+    let table = file::decode(buf, opts.verify)?;
+    if opts.verify {
+        file::verify(&table)?;
+    }
+    let file::TableFile {
+        footer,
+        chunks,
+        meta,
+        sstable,
+    } = table;
+
+    println!("file: {} ({} bytes)", opts.file, size);
+    if opts.verify {
+        println!("checksums: ok");
+        println!("keys: ok");
+    }
+
+    println!("footer:");
+    println!("  version: {}", footer.version);
+    print_handle("data", &footer.data);
+    print_handle("index", &footer.index);
+    print_handle("filter", &footer.filter);
+    print_handle("tombstones", &footer.tombstones);
+    print_handle("meta", &footer.meta);
+
+    println!("data chunks:");
+    println!("  compression: {:?}", sstable.compression);
+    for (i, chunk) in chunks.iter().enumerate() {
+        println!(
+            "  #{}: {:?} {} -> {} bytes",
+            i, chunk.compression, chunk.uncompressed_len, chunk.compressed_len
+        );
+    }
+
+    println!("meta:");
+    println!("  entries: {}", meta.entries);
+    println!("  range_tombstones: {}", meta.range_tombstones);
+    println!("  min_timestamp: {}", meta.min_timestamp);
+    println!("  max_timestamp: {}", meta.max_timestamp);
+
+    println!("filter:");
+    println!("  kind: {:?}", sstable.filter.kind());
+    println!(
+        "  prefix_extractor: {:?}",
+        sstable.filter.prefix_extractor()
+    );
+    match sstable.filter.kind() {
+        FilterKind::Bloom | FilterKind::BlockedBloom => println!("  k: {}", sstable.filter.k()),
+        FilterKind::Cuckoo | FilterKind::Xor => {
+            println!("  fingerprint_bits: {}", sstable.filter.k())
+        }
+        FilterKind::None => {}
+    }
+    println!("  m: {}", sstable.filter.m());
+    println!(
+        "  estimated_fpr: {:.6}",
+        sstable.filter.false_positive_rate(meta.entries as usize)
+    );
+
+    println!("index (restart points):");
+    for (key, offset) in sstable.index.iter() {
+        println!("  {} @{} -> {}", row(key.row()), key.timestamp(), offset);
+    }
+
+    println!("tombstones:");
+    for t in sstable.tombstones.iter() {
+        println!(
+            "  [{}, {}) @{}",
+            row(t.start()),
+            row(t.end()),
+            t.timestamp()
+        );
+    }
+
+    println!("records:");
+    for (offset, key, value) in sstable.data.iter().filter(|(_, k, _)| opts.matches(k)) {
+        let value = match value {
+            Value::Val(v) => format!("\"{}\"", escape(&v)),
+            Value::Del => "<deleted>".to_string(),
+        };
+        println!(
+            "  {}: {} @{} => {}",
+            offset,
+            row(key.row()),
+            key.timestamp(),
+            value
+        );
+    }
+
+    Ok(())
+}
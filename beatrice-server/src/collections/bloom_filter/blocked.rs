@@ -0,0 +1,204 @@
+use super::{
+    hash::{hash64, mix, FxBuildHasher},
+    optimal_k,
+};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Number of bits in a block, which fits in a cache line
+const BLOCK_BITS: usize = 512;
+const BLOCK_WORDS: usize = BLOCK_BITS / 64;
+
+type Block = [u64; BLOCK_WORDS];
+
+/// A bloom-filter which sets and probes all the bits of an item in a single block
+/// so that a lookup touches only one cache line.
+///
+/// It trades a slightly higher false positive rate for fewer cache misses
+/// than [`BloomFilter`](super::BloomFilter).
+#[derive(Clone)]
+pub struct BlockedBloomFilter<T, B = FxBuildHasher> {
+    /// number of hash functions
+    k: usize,
+    blocks: Vec<Block>,
+    build_hasher: B,
+    _t: PhantomData<T>,
+}
+
+impl<T> BlockedBloomFilter<T>
+where
+    T: Hash,
+{
+    /// Create a new blocked bloom-filter where
+    /// - `n`: number of items
+    /// - `m`: number of bits, rounded up to a multiple of the block size
+    pub fn new(n: u64, m: u64) -> Self {
+        let num_blocks = ((m as usize + BLOCK_BITS - 1) / BLOCK_BITS).max(1);
+        Self {
+            k: optimal_k(n, m),
+            blocks: vec![[0; BLOCK_WORDS]; num_blocks],
+            build_hasher: FxBuildHasher::default(),
+            _t: PhantomData,
+        }
+    }
+
+    /// Restore a blocked bloom-filter from `k` and the bytes returned by
+    /// [`BlockedBloomFilter::to_bytes`].
+    pub fn from_bytes(k: usize, m: u64, bytes: &[u8]) -> Self {
+        let mut filter = Self::new(1, m);
+        filter.k = k;
+        for (block, chunk) in filter.blocks.iter_mut().zip(bytes.chunks(BLOCK_BITS / 8)) {
+            for (word, b) in block.iter_mut().zip(chunk.chunks(8)) {
+                let mut buf = [0; 8];
+                buf[..b.len()].copy_from_slice(b);
+                *word = u64::from_le_bytes(buf);
+            }
+        }
+        filter
+    }
+}
+
+impl<T, B> BlockedBloomFilter<T, B>
+where
+    T: Hash,
+    B: BuildHasher,
+{
+    /// Number of hash functions
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Number of bits
+    pub fn m(&self) -> usize {
+        self.blocks.len() * BLOCK_BITS
+    }
+
+    /// Pack the blocks into bytes in little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.iter())
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    /// Estimated false positive rate after `n` items are inserted.
+    ///
+    /// Since the number of items in a block follows a Poisson distribution,
+    /// the rate is the average of those of standard bloom-filters of a block size.
+    pub fn false_positive_rate(&self, n: usize) -> f64 {
+        let lambda = n as f64 / self.blocks.len() as f64;
+        let k = self.k as f64;
+        let bits = BLOCK_BITS as f64;
+
+        // accumulate the probabilities in log space to avoid underflow
+        let mut rate = 0.0;
+        let mut ln_factorial = 0.0;
+        let max = (lambda + 10.0 * lambda.sqrt() + 10.0) as usize;
+        for i in 0..=max {
+            if i > 0 {
+                ln_factorial += (i as f64).ln();
+            }
+            let p = (i as f64 * lambda.ln() - lambda - ln_factorial).exp();
+            let fpr = (1.0 - (1.0 - 1.0 / bits).powf(k * i as f64)).powf(k);
+            rate += p * fpr;
+        }
+        rate
+    }
+
+    pub fn insert<V>(&mut self, value: V)
+    where
+        V: Borrow<T>,
+    {
+        let (block, probes) = self.probes(value);
+        let block = &mut self.blocks[block];
+        for bit in probes {
+            block[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains<V>(&self, value: V) -> bool
+    where
+        V: Borrow<T>,
+    {
+        let (block, mut probes) = self.probes(value);
+        let block = &self.blocks[block];
+        probes.all(|bit| block[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Returns the index of the block and the bits in the block for `value`.
+    fn probes<V>(&self, value: V) -> (usize, impl Iterator<Item = usize>)
+    where
+        V: Borrow<T>,
+    {
+        let h = hash64(&self.build_hasher, value.borrow());
+
+        let block = (((h >> 32) * self.blocks.len() as u64) >> 32) as usize;
+        let g = mix(h);
+        let h1 = g as u32;
+        let h2 = (g >> 32) as u32 | 1;
+        let shift = 32 - BLOCK_BITS.trailing_zeros();
+        let probes = (0..self.k as u32)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) >> shift) as usize);
+        (block, probes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let filter = BlockedBloomFilter::<u64>::new(2048, 30000);
+        assert!(!filter.contains(&10));
+    }
+
+    #[test]
+    fn same() {
+        let mut filter = BlockedBloomFilter::<u64>::new(2048, 30000);
+        for i in 0..2048 {
+            filter.insert(i);
+        }
+        assert!((0..2048).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let n = 10000;
+        let mut filter = BlockedBloomFilter::<u64>::new(n, n * 10);
+        for i in 0..n {
+            filter.insert(i);
+        }
+        let positives = (n..n * 11).filter(|i| filter.contains(i)).count();
+        let actual = positives as f64 / (n * 10) as f64;
+        let expected = filter.false_positive_rate(n as usize);
+        // 10 bits per item give about 1% for a standard bloom-filter
+        assert!(expected > 0.008 && expected < 0.02, "expected={}", expected);
+        assert!(
+            (actual - expected).abs() < expected * 0.3,
+            "actual={}, expected={}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        let mut filter = BlockedBloomFilter::<u64>::new(100, 1000);
+        for i in 0..100 {
+            filter.insert(i);
+        }
+        let restored = BlockedBloomFilter::<u64>::from_bytes(
+            filter.k(),
+            filter.m() as u64,
+            &filter.to_bytes(),
+        );
+        assert_eq!(1024, restored.m());
+        assert_eq!(filter.to_bytes(), restored.to_bytes());
+        assert!((0..100).all(|i| restored.contains(&i)));
+    }
+}
@@ -0,0 +1,202 @@
+use super::{
+    hash::{hash64, mix, FxBuildHasher},
+    optimal_k,
+};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// A bloom-filter with counters instead of bits, which supports removal of items.
+///
+/// A counter which reaches its maximum sticks there since the number of items it counts
+/// is no longer known. Removing an item then leaves it behind rather than risking false negatives.
+#[derive(Clone)]
+pub struct CountingBloomFilter<T, B = FxBuildHasher> {
+    /// number of hash functions
+    k: usize,
+    counters: Vec<u8>,
+    build_hasher: B,
+    _t: PhantomData<T>,
+}
+
+impl<T> CountingBloomFilter<T>
+where
+    T: Hash,
+{
+    /// Create a new counting bloom-filter where
+    /// - `n`: number of items
+    /// - `m`: number of counters
+    pub fn new(n: u64, m: u64) -> Self {
+        Self {
+            k: optimal_k(n, m),
+            counters: vec![0; m.max(1) as usize],
+            build_hasher: FxBuildHasher::default(),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T, B> CountingBloomFilter<T, B>
+where
+    T: Hash,
+    B: BuildHasher,
+{
+    /// Number of hash functions
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Number of counters
+    pub fn m(&self) -> usize {
+        self.counters.len()
+    }
+
+    /// Number of counters stuck at the maximum
+    pub fn saturated(&self) -> usize {
+        self.counters
+            .iter()
+            .filter(|&&c| c == u8::max_value())
+            .count()
+    }
+
+    /// Estimated false positive rate with `n` items.
+    pub fn false_positive_rate(&self, n: usize) -> f64 {
+        let k = self.k as f64;
+        let m = self.counters.len() as f64;
+        (1.0 - (-k * n as f64 / m).exp()).powf(k)
+    }
+
+    pub fn insert<V>(&mut self, value: V)
+    where
+        V: Borrow<T>,
+    {
+        for i in self.probes(value) {
+            let c = &mut self.counters[i];
+            *c = c.saturating_add(1);
+        }
+    }
+
+    /// Remove `value` inserted before.
+    /// Returns `false` without changing anything if `value` is definitely not in the filter.
+    pub fn remove<V>(&mut self, value: V) -> bool
+    where
+        V: Borrow<T>,
+    {
+        let probes = self.probes(value).collect::<Vec<_>>();
+        if probes.iter().any(|&i| self.counters[i] == 0) {
+            return false;
+        }
+        for i in probes {
+            let c = &mut self.counters[i];
+            if *c != u8::max_value() {
+                *c -= 1;
+            }
+        }
+        true
+    }
+
+    pub fn contains<V>(&self, value: V) -> bool
+    where
+        V: Borrow<T>,
+    {
+        self.probes(value).all(|i| self.counters[i] != 0)
+    }
+
+    fn probes<V>(&self, value: V) -> impl Iterator<Item = usize>
+    where
+        V: Borrow<T>,
+    {
+        let h1 = hash64(&self.build_hasher, value.borrow());
+        let h2 = mix(h1) | 1;
+        let m = self.counters.len() as u64;
+        (0..self.k as u64).map(move |i| {
+            let h = h1.wrapping_add(i.wrapping_mul(h2));
+            (((h >> 32) * m) >> 32) as usize
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let filter = CountingBloomFilter::<u64>::new(2048, 20480);
+        assert!(!filter.contains(&10));
+    }
+
+    #[test]
+    fn same() {
+        let mut filter = CountingBloomFilter::<u64>::new(2048, 20480);
+        for i in 0..2048 {
+            filter.insert(i);
+        }
+        assert!((0..2048).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn remove() {
+        let mut filter = CountingBloomFilter::<u64>::new(2048, 20480);
+        for i in 0..2048 {
+            filter.insert(i);
+        }
+        for i in (0..2048).step_by(2) {
+            assert!(filter.remove(i));
+        }
+        assert!((1..2048).step_by(2).all(|i| filter.contains(&i)));
+        let positives = (0..2048).step_by(2).filter(|i| filter.contains(i)).count();
+        assert!(positives < 1024 / 20, "positives={}", positives);
+
+        for i in (1..2048).step_by(2) {
+            assert!(filter.remove(i));
+        }
+        assert!(filter.counters.iter().all(|&c| c == 0));
+        assert!(!filter.remove(1));
+    }
+
+    #[test]
+    fn saturation() {
+        let mut filter = CountingBloomFilter::<u64>::new(16, 160);
+        let n = u8::max_value() as usize + 10;
+        for _ in 0..n {
+            filter.insert(1);
+        }
+        assert_eq!(filter.k(), filter.saturated());
+
+        // saturated counters are never decremented
+        for _ in 0..n {
+            assert!(filter.remove(1));
+        }
+        assert!(filter.contains(&1));
+        assert_eq!(filter.k(), filter.saturated());
+
+        // other items sharing the saturated counters are still found after removal
+        filter.insert(2);
+        filter.remove(2);
+        filter.insert(2);
+        assert!(filter.contains(&2));
+        assert!(filter.remove(2));
+        assert!(filter.contains(&1));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let n = 10000;
+        let mut filter = CountingBloomFilter::<u64>::new(n, n * 10);
+        for i in 0..n {
+            filter.insert(i);
+        }
+        let positives = (n..n * 11).filter(|i| filter.contains(i)).count();
+        let actual = positives as f64 / (n * 10) as f64;
+        let expected = filter.false_positive_rate(n as usize);
+        assert!(
+            (actual - expected).abs() < expected * 0.3,
+            "actual={}, expected={}",
+            actual,
+            expected
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use std::{borrow::Borrow, hash::Hash};
+use rustc_hash::FxHasher;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
+};
+
+pub type FxBuildHasher = BuildHasherDefault<FxHasher>;
 
 pub trait Hashers {
     type H: Hashes;
@@ -33,4 +39,21 @@ where
     }
 }
 
+/// Hash `value` into 64 bits, spreading the entropy of weak hashes over all the bits.
+pub fn hash64<T: Hash + ?Sized, B: BuildHasher>(build_hasher: &B, value: &T) -> u64 {
+    let mut hasher = build_hasher.build_hasher();
+    value.hash(&mut hasher);
+    mix(hasher.finish())
+}
+
+/// Finalizer of MurmurHash3
+pub fn mix(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    h
+}
+
 pub mod km;
@@ -1,4 +1,5 @@
-use self::hash::{km::KMHashers, Hashers, Hashes};
+use self::hash::{km::KMHashers, FxBuildHasher, Hashers, Hashes};
+pub use self::{blocked::BlockedBloomFilter, counting::CountingBloomFilter};
 use bitvec::{bitvec, vec::BitVec};
 use rustc_hash::FxHasher;
 use std::{
@@ -8,9 +9,12 @@ use std::{
     marker::PhantomData,
 };
 
+mod blocked;
+mod counting;
 pub mod hash;
 
-type FxBuildHasher = BuildHasherDefault<FxHasher>;
+/// Maximum number of hash functions, beyond which more bits per item barely help
+pub const MAX_K: usize = 64;
 
 #[derive(Clone)]
 pub struct BloomFilter<T, H = KMHashers<FxBuildHasher, FxBuildHasher>> {
@@ -39,6 +43,18 @@ where
             ),
         )
     }
+
+    /// Restore a bloom-filter from `k` and the bits packed by [`BloomFilter::to_bytes`].
+    pub fn from_bytes(k: usize, m: u64, bytes: &[u8]) -> Self {
+        let mut filter = Self::new(1, m);
+        filter.k = k;
+        for i in 0..m as usize {
+            if bytes[i / 8] & (1 << (i % 8)) != 0 {
+                filter.bits.set(i, true);
+            }
+        }
+        filter
+    }
 }
 
 impl<T, H> BloomFilter<T, H>
@@ -47,15 +63,42 @@ where
     H: Hashers,
 {
     pub fn with_hashers(n: u64, m: u64, hashers: H) -> Self {
-        let k = ((m as f64) / (n as f64) * LN_2) as usize;
         Self {
-            k,
+            k: optimal_k(n, m),
             bits: bitvec![0; m as usize],
             hashers,
             _t: PhantomData,
         }
     }
 
+    /// Number of hash functions
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Number of bits
+    pub fn m(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Pack the bits into bytes, least significant bit first.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; (self.bits.len() + 7) / 8];
+        for (i, bit) in self.bits.iter().enumerate() {
+            if *bit {
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+        }
+        bytes
+    }
+
+    /// Estimated false positive rate after `n` items are inserted.
+    pub fn false_positive_rate(&self, n: usize) -> f64 {
+        let k = self.k as f64;
+        let m = self.bits.len() as f64;
+        (1.0 - (-k * n as f64 / m).exp()).powf(k)
+    }
+
     pub fn insert<V>(&mut self, value: V)
     where
         V: Borrow<T>,
@@ -75,6 +118,14 @@ where
     }
 }
 
+/// Number of hash functions minimizing the false positive rate of `m` bits for `n` items
+fn optimal_k(n: u64, m: u64) -> usize {
+    ((m as f64) / (n as f64) * LN_2)
+        .round()
+        .max(1.0)
+        .min(MAX_K as f64) as usize
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,4 +155,30 @@ mod tests {
         filter.insert(10);
         assert!(filter.contains(&10));
     }
+
+    #[test]
+    fn k() {
+        // 9.6 bits per item
+        assert_eq!(7, optimal_k(10, 96));
+        assert_eq!(6, optimal_k(10, 90));
+        // too few bits still need a hash function to reject anything
+        assert_eq!(1, optimal_k(100, 10));
+        let mut filter = BloomFilter::<u64>::new(100, 10);
+        filter.insert(1);
+        assert!(!(0..100).all(|i| filter.contains(i)));
+    }
+
+    #[test]
+    fn bytes() {
+        let mut filter = BloomFilter::<u64>::new(16, 100);
+        for i in 0..16 {
+            filter.insert(i);
+        }
+        let restored = BloomFilter::<u64>::from_bytes(filter.k(), 100, &filter.to_bytes());
+        assert_eq!(filter.k(), restored.k());
+        assert_eq!(filter.to_bytes(), restored.to_bytes());
+        for i in 0..16 {
+            assert!(restored.contains(&i));
+        }
+    }
 }
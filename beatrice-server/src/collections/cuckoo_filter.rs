@@ -0,0 +1,274 @@
+//! Cuckoo filter by Fan et al., which stores a fingerprint of each item
+//! in one of its two candidate buckets.
+
+use super::{
+    bloom_filter::hash::{hash64, mix, FxBuildHasher},
+    fingerprints::Fingerprints,
+};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Number of fingerprints in a bucket
+const BUCKET_SIZE: usize = 4;
+/// Fraction of occupied slots the filter is sized for
+pub const LOAD_FACTOR: f64 = 0.9;
+/// Maximum number of relocations on an insertion
+const MAX_KICKS: usize = 500;
+/// Maximum number of times [`CuckooFilter::build`] grows the table
+const MAX_GROWTHS: usize = 64;
+
+#[derive(Clone)]
+pub struct CuckooFilter<T, B = FxBuildHasher> {
+    num_buckets: usize,
+    /// `BUCKET_SIZE` fingerprints for each bucket, where 0 denotes an empty slot
+    fingerprints: Fingerprints,
+    /// A fingerprint and its bucket which could not be placed
+    victim: Option<(usize, u64)>,
+    build_hasher: B,
+    _t: PhantomData<T>,
+}
+
+impl<T> CuckooFilter<T>
+where
+    T: Hash,
+{
+    /// Create a new cuckoo filter for `n` items with fingerprints of `bits` bits.
+    pub fn new(n: u64, bits: u32) -> Self {
+        let num_buckets = (n as f64 / (BUCKET_SIZE as f64 * LOAD_FACTOR)).ceil() as usize;
+        Self::with_buckets(num_buckets.max(1), bits)
+    }
+
+    /// Build a cuckoo filter of `values` with fingerprints of `bits` bits,
+    /// growing the table until all of them are placed.
+    ///
+    /// Returns `None` if they are not placed after `MAX_GROWTHS` growths,
+    /// which happens if more than `2 * BUCKET_SIZE` values share a fingerprint and buckets,
+    /// e.g. if `values` has duplicates.
+    pub fn build<V>(values: &[V], bits: u32) -> Option<Self>
+    where
+        V: Borrow<T>,
+    {
+        let mut filter = Self::new(values.len() as u64, bits);
+        for _ in 0..MAX_GROWTHS {
+            for value in values {
+                if !filter.insert(value.borrow()) {
+                    break;
+                }
+            }
+            if filter.victim.is_none() {
+                return Some(filter);
+            }
+            let num_buckets = filter.num_buckets + filter.num_buckets / 8 + 1;
+            filter = Self::with_buckets(num_buckets, bits);
+        }
+        None
+    }
+
+    /// Restore a cuckoo filter of `m` bits of fingerprints of `bits` bits from the bytes
+    /// returned by [`CuckooFilter::to_bytes`].
+    pub fn from_bytes(bits: u32, m: u64, bytes: &[u8]) -> Option<Self> {
+        let bucket_bits = BUCKET_SIZE as u64 * bits as u64;
+        if bits == 0 || m == 0 || m % bucket_bits != 0 {
+            return None;
+        }
+        let len = (m / bits as u64) as usize;
+        Some(Self {
+            num_buckets: len / BUCKET_SIZE,
+            fingerprints: Fingerprints::from_bytes(bits, len, bytes)?,
+            victim: None,
+            build_hasher: FxBuildHasher::default(),
+            _t: PhantomData,
+        })
+    }
+
+    fn with_buckets(num_buckets: usize, bits: u32) -> Self {
+        Self {
+            num_buckets,
+            fingerprints: Fingerprints::new(bits, num_buckets * BUCKET_SIZE),
+            victim: None,
+            build_hasher: FxBuildHasher::default(),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T, B> CuckooFilter<T, B>
+where
+    T: Hash,
+    B: BuildHasher,
+{
+    /// Number of bits of a fingerprint
+    pub fn bits(&self) -> u32 {
+        self.fingerprints.bits()
+    }
+
+    /// Number of bits of the buckets
+    pub fn m(&self) -> usize {
+        self.fingerprints.len() * self.bits() as usize
+    }
+
+    /// Pack the buckets into bytes. The victim is not included.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.fingerprints.to_bytes()
+    }
+
+    /// Estimated false positive rate after `n` items are inserted.
+    pub fn false_positive_rate(&self, n: usize) -> f64 {
+        // a lookup compares the fingerprint with the occupied slots of two buckets,
+        // each of which matches with a probability of 1 / (2^bits - 1)
+        let load = n as f64 / self.fingerprints.len() as f64;
+        let p = 1.0 / (self.fingerprints.mask() as f64);
+        1.0 - (1.0 - p).powf(2.0 * BUCKET_SIZE as f64 * load.min(1.0))
+    }
+
+    /// Insert `value`.
+    /// Returns `false` if the filter is full, after which no more values can be inserted.
+    /// Inserted values are still found even then.
+    pub fn insert<V>(&mut self, value: V) -> bool
+    where
+        V: Borrow<T>,
+    {
+        if self.victim.is_some() {
+            return false;
+        }
+        let (i1, mut fingerprint) = self.index_and_fingerprint(value);
+        let i2 = self.alt_index(i1, fingerprint);
+        if self.put(i1, fingerprint) || self.put(i2, fingerprint) {
+            return true;
+        }
+
+        let mut i = if fingerprint & 1 == 0 { i1 } else { i2 };
+        for kick in 0..MAX_KICKS {
+            let slot = i * BUCKET_SIZE + (mix(fingerprint ^ kick as u64) as usize % BUCKET_SIZE);
+            let evicted = self.fingerprints.get(slot);
+            self.fingerprints.set(slot, fingerprint);
+            fingerprint = evicted;
+            i = self.alt_index(i, fingerprint);
+            if self.put(i, fingerprint) {
+                return true;
+            }
+        }
+        self.victim = Some((i, fingerprint));
+        false
+    }
+
+    pub fn contains<V>(&self, value: V) -> bool
+    where
+        V: Borrow<T>,
+    {
+        let (i1, fingerprint) = self.index_and_fingerprint(value);
+        let i2 = self.alt_index(i1, fingerprint);
+        let found = |i: usize| {
+            (i * BUCKET_SIZE..(i + 1) * BUCKET_SIZE)
+                .any(|slot| self.fingerprints.get(slot) == fingerprint)
+        };
+        found(i1)
+            || found(i2)
+            || self
+                .victim
+                .map_or(false, |(i, f)| f == fingerprint && (i == i1 || i == i2))
+    }
+
+    fn index_and_fingerprint<V>(&self, value: V) -> (usize, u64)
+    where
+        V: Borrow<T>,
+    {
+        let h = hash64(&self.build_hasher, value.borrow());
+        let fingerprint = ((h >> 32) & self.fingerprints.mask()).max(1);
+        (reduce(h as u32, self.num_buckets), fingerprint)
+    }
+
+    /// The other bucket of `fingerprint` in bucket `i`.
+    ///
+    /// `(hash(fingerprint) - i) mod num_buckets` is its own inverse
+    /// for any number of buckets.
+    fn alt_index(&self, i: usize, fingerprint: u64) -> usize {
+        let h = reduce(mix(fingerprint) as u32, self.num_buckets);
+        (h + self.num_buckets - i) % self.num_buckets
+    }
+
+    /// Put `fingerprint` in an empty slot of bucket `i` if any.
+    fn put(&mut self, i: usize, fingerprint: u64) -> bool {
+        let empty =
+            (i * BUCKET_SIZE..(i + 1) * BUCKET_SIZE).find(|&slot| self.fingerprints.get(slot) == 0);
+        match empty {
+            Some(slot) => {
+                self.fingerprints.set(slot, fingerprint);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Maps `x` to `0..n` fairly without a division
+fn reduce(x: u32, n: usize) -> usize {
+    ((x as u64 * n as u64) >> 32) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let filter = CuckooFilter::<u64>::new(2048, 12);
+        assert!(!filter.contains(&10));
+    }
+
+    #[test]
+    fn same() {
+        let mut filter = CuckooFilter::<u64>::new(2048, 12);
+        for i in 0..2048 {
+            assert!(filter.insert(i));
+        }
+        assert!((0..2048).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn full() {
+        let mut filter = CuckooFilter::<u64>::new(100, 12);
+        let n = (0..).take_while(|&i| filter.insert(i)).count() as u64;
+        assert!(n >= 100, "n={}", n);
+        assert!(!filter.insert(n + 1));
+        // including the value which failed to be placed
+        assert!((0..=n).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let n = 10000;
+        let values = (0..n).collect::<Vec<u64>>();
+        let filter = CuckooFilter::<u64>::build(&values, 12).unwrap();
+        let positives = (n..n * 11).filter(|i| filter.contains(i)).count();
+        let actual = positives as f64 / (n * 10) as f64;
+        let expected = filter.false_positive_rate(n as usize);
+        assert!(
+            (actual - expected).abs() < expected * 0.3,
+            "actual={}, expected={}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        assert!(CuckooFilter::<u64>::build(&[1; 9], 12).is_none());
+        assert!(CuckooFilter::<u64>::build(&[1, 1, 2], 12).is_some());
+    }
+
+    #[test]
+    fn bytes() {
+        let values = (0..100).collect::<Vec<u64>>();
+        let filter = CuckooFilter::<u64>::build(&values, 10).unwrap();
+        let restored =
+            CuckooFilter::<u64>::from_bytes(filter.bits(), filter.m() as u64, &filter.to_bytes())
+                .unwrap();
+        assert_eq!(filter.to_bytes(), restored.to_bytes());
+        assert!(values.iter().all(|i| restored.contains(i)));
+        assert!(CuckooFilter::<u64>::from_bytes(10, filter.m() as u64 + 40, &[]).is_none());
+    }
+}
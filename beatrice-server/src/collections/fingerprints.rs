@@ -0,0 +1,121 @@
+/// A fixed-length array of fingerprints of `bits` bits packed into words
+#[derive(Clone)]
+pub struct Fingerprints {
+    bits: u32,
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl Fingerprints {
+    pub fn new(bits: u32, len: usize) -> Self {
+        assert!((1..=32).contains(&bits), "bits must be in 1..=32: {}", bits);
+        Self {
+            bits,
+            len,
+            words: vec![0; (len * bits as usize + 63) / 64],
+        }
+    }
+
+    /// Restore fingerprints from the bytes returned by [`Fingerprints::to_bytes`].
+    /// Returns `None` if the length of `bytes` does not match.
+    pub fn from_bytes(bits: u32, len: usize, bytes: &[u8]) -> Option<Self> {
+        if !(1..=32).contains(&bits) || bytes.len() != Self::size(bits, len) {
+            return None;
+        }
+        let mut fingerprints = Self::new(bits, len);
+        for (word, b) in fingerprints.words.iter_mut().zip(bytes.chunks(8)) {
+            let mut buf = [0; 8];
+            buf[..b.len()].copy_from_slice(b);
+            *word = u64::from_le_bytes(buf);
+        }
+        Some(fingerprints)
+    }
+
+    /// Number of bytes of `len` fingerprints of `bits` bits
+    pub fn size(bits: u32, len: usize) -> usize {
+        (len * bits as usize + 7) / 8
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    pub fn get(&self, i: usize) -> u64 {
+        let (word, offset) = self.position(i);
+        let mut fingerprint = self.words[word] >> offset;
+        if offset + self.bits > 64 {
+            fingerprint |= self.words[word + 1] << (64 - offset);
+        }
+        fingerprint & self.mask()
+    }
+
+    pub fn set(&mut self, i: usize, fingerprint: u64) {
+        let mask = self.mask();
+        let fingerprint = fingerprint & mask;
+        let (word, offset) = self.position(i);
+        self.words[word] = (self.words[word] & !(mask << offset)) | (fingerprint << offset);
+        if offset + self.bits > 64 {
+            let shift = 64 - offset;
+            self.words[word + 1] =
+                (self.words[word + 1] & !(mask >> shift)) | (fingerprint >> shift);
+        }
+    }
+
+    /// Pack the fingerprints into bytes in little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self
+            .words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        bytes.truncate(Self::size(self.bits, self.len));
+        bytes
+    }
+
+    fn position(&self, i: usize) -> (usize, u32) {
+        debug_assert!(i < self.len);
+        let bit = i * self.bits as usize;
+        (bit / 64, (bit % 64) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        for &bits in [1, 7, 8, 13, 32].iter() {
+            let mut fingerprints = Fingerprints::new(bits, 100);
+            for i in 0..100 {
+                fingerprints.set(i, i as u64 * 0x9e37_79b9);
+            }
+            for i in (0..100).step_by(3) {
+                fingerprints.set(i, !0);
+            }
+            let mask = fingerprints.mask();
+            for i in 0..100 {
+                let expected = if i % 3 == 0 {
+                    mask
+                } else {
+                    (i as u64 * 0x9e37_79b9) & mask
+                };
+                assert_eq!(expected, fingerprints.get(i), "bits={}, i={}", bits, i);
+            }
+
+            let bytes = fingerprints.to_bytes();
+            assert_eq!(Fingerprints::size(bits, 100), bytes.len());
+            let restored = Fingerprints::from_bytes(bits, 100, &bytes).unwrap();
+            assert!((0..100).all(|i| fingerprints.get(i) == restored.get(i)));
+            assert!(Fingerprints::from_bytes(bits, 108, &bytes).is_none());
+        }
+    }
+}
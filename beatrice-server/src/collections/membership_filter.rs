@@ -0,0 +1,108 @@
+use super::{
+    bloom_filter::{hash::Hashers, BlockedBloomFilter, BloomFilter},
+    cuckoo_filter::CuckooFilter,
+    xor_filter::XorFilter,
+};
+use std::hash::{BuildHasher, Hash};
+
+/// A probabilistic set which may report false positives but no false negatives
+pub trait MembershipFilter<T> {
+    /// Returns `false` if `value` is definitely not in the set.
+    fn contains(&self, value: &T) -> bool;
+
+    /// Number of bits
+    fn m(&self) -> usize;
+
+    /// Estimated false positive rate with `n` items
+    fn false_positive_rate(&self, n: usize) -> f64;
+
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
+impl<T, H> MembershipFilter<T> for BloomFilter<T, H>
+where
+    T: Hash,
+    H: Hashers,
+{
+    fn contains(&self, value: &T) -> bool {
+        BloomFilter::contains(self, value)
+    }
+
+    fn m(&self) -> usize {
+        BloomFilter::m(self)
+    }
+
+    fn false_positive_rate(&self, n: usize) -> f64 {
+        BloomFilter::false_positive_rate(self, n)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        BloomFilter::to_bytes(self)
+    }
+}
+
+impl<T, B> MembershipFilter<T> for BlockedBloomFilter<T, B>
+where
+    T: Hash,
+    B: BuildHasher,
+{
+    fn contains(&self, value: &T) -> bool {
+        BlockedBloomFilter::contains(self, value)
+    }
+
+    fn m(&self) -> usize {
+        BlockedBloomFilter::m(self)
+    }
+
+    fn false_positive_rate(&self, n: usize) -> f64 {
+        BlockedBloomFilter::false_positive_rate(self, n)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        BlockedBloomFilter::to_bytes(self)
+    }
+}
+
+impl<T, B> MembershipFilter<T> for CuckooFilter<T, B>
+where
+    T: Hash,
+    B: BuildHasher,
+{
+    fn contains(&self, value: &T) -> bool {
+        CuckooFilter::contains(self, value)
+    }
+
+    fn m(&self) -> usize {
+        CuckooFilter::m(self)
+    }
+
+    fn false_positive_rate(&self, n: usize) -> f64 {
+        CuckooFilter::false_positive_rate(self, n)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        CuckooFilter::to_bytes(self)
+    }
+}
+
+impl<T, B> MembershipFilter<T> for XorFilter<T, B>
+where
+    T: Hash,
+    B: BuildHasher,
+{
+    fn contains(&self, value: &T) -> bool {
+        XorFilter::contains(self, value)
+    }
+
+    fn m(&self) -> usize {
+        XorFilter::m(self)
+    }
+
+    fn false_positive_rate(&self, _n: usize) -> f64 {
+        XorFilter::false_positive_rate(self)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        XorFilter::to_bytes(self)
+    }
+}
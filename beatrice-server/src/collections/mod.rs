@@ -1,2 +1,6 @@
 pub mod bloom_filter;
+pub mod cuckoo_filter;
+mod fingerprints;
+pub mod membership_filter;
 pub mod skip_list;
+pub mod xor_filter;
@@ -107,15 +107,37 @@ where
     }
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_with_neighbors(key, value).0
+    }
+
+    /// Inserts `key` like [`insert`](Self::insert) and returns the old value
+    /// along with the keys right before and after `key`, found by the same search.
+    pub fn insert_with_neighbors(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> (Option<V>, Option<&K>, Option<&K>) {
         let trace = self.search_iter(&key).collect::<Vec<_>>();
+        let prev = trace.last().copied().flatten();
 
-        if let Some(next) = trace.last().and_then(|&id| self.next(id)) {
-            let node = self.node_mut(next);
-            if node.key == key {
-                return Some(std::mem::replace(&mut node.value, value));
+        let (old_value, id) = match trace.last().and_then(|&id| self.next(id)) {
+            Some(next) if self.node(next).key == key => {
+                let node = self.node_mut(next);
+                (Some(std::mem::replace(&mut node.value, value)), next)
             }
-        }
+            _ => (None, self.insert_node(trace, key, value)),
+        };
 
+        let list = &*self;
+        let next = list.node(id).forwards[0];
+        (
+            old_value,
+            prev.map(|i| &list.node(i).key),
+            next.map(|i| &list.node(i).key),
+        )
+    }
+
+    fn insert_node(&mut self, trace: Vec<Option<NonZeroUsize>>, key: K, value: V) -> NonZeroUsize {
         let new_level = self.generator.generate();
         let id = self.register(Node::new(key, value, new_level));
 
@@ -141,13 +163,20 @@ where
                 }
             }
         }
-
-        None
+        id
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
         Iter::new(self)
     }
+
+    /// Returns an iterator over the entries whose keys are greater than or equal to `key`.
+    pub fn iter_from(&self, key: &K) -> impl Iterator<Item = (&K, &V)> {
+        Iter {
+            list: self,
+            id: self.get_smallest_id(key),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -396,4 +425,34 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn iter_from() {
+        let mut list = SkipListMap::new(4);
+        for k in &[10u8, 30, 20, 50, 40] {
+            list.insert(*k, ());
+        }
+
+        let keys = |from| list.iter_from(&from).map(|(&k, _)| k).collect::<Vec<_>>();
+        assert_eq!(vec![10, 20, 30, 40, 50], keys(0));
+        assert_eq!(vec![30, 40, 50], keys(30));
+        assert_eq!(vec![40, 50], keys(31));
+        assert!(keys(51).is_empty());
+    }
+
+    #[test]
+    fn insert_with_neighbors() {
+        let mut list = SkipListMap::new(4);
+        assert_eq!((None, None, None), list.insert_with_neighbors(20u8, 'a'));
+        assert_eq!((None, None, Some(&20)), list.insert_with_neighbors(10, 'b'));
+        assert_eq!((None, Some(&20), None), list.insert_with_neighbors(40, 'c'));
+        assert_eq!(
+            (None, Some(&20), Some(&40)),
+            list.insert_with_neighbors(30, 'd')
+        );
+        assert_eq!(
+            (Some('d'), Some(&20), Some(&40)),
+            list.insert_with_neighbors(30, 'e')
+        );
+    }
 }
@@ -0,0 +1,240 @@
+//! Xor filter by Graf and Lemire, which takes about `1.23 * bits` bits per item
+//! for a false positive rate of `2^-bits`.
+//!
+//! Unlike bloom-filters, all the items must be given on construction.
+
+use super::{
+    bloom_filter::hash::{hash64, mix, FxBuildHasher},
+    fingerprints::Fingerprints,
+};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Number of slots per item
+pub const SPACE_FACTOR: f64 = 1.23;
+
+#[derive(Clone)]
+pub struct XorFilter<T, B = FxBuildHasher> {
+    seed: u64,
+    /// Number of slots for each of the three hash functions
+    block_len: usize,
+    fingerprints: Fingerprints,
+    build_hasher: B,
+    _t: PhantomData<T>,
+}
+
+impl<T> XorFilter<T>
+where
+    T: Hash,
+{
+    /// Build an xor filter of `values` with fingerprints of `bits` bits.
+    pub fn build<V>(values: &[V], bits: u32) -> Self
+    where
+        V: Borrow<T>,
+    {
+        let build_hasher = FxBuildHasher::default();
+        let mut hashes = values
+            .iter()
+            .map(|value| hash64(&build_hasher, value.borrow()))
+            .collect::<Vec<_>>();
+        // duplicate hashes never get a slot of their own
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        let block_len = (32 + (hashes.len() as f64 * SPACE_FACTOR).ceil() as usize) / 3;
+        let mut attempt = 0;
+        loop {
+            let seed = mix(attempt);
+            if let Some(fingerprints) = construct(&hashes, seed, block_len, bits) {
+                return Self {
+                    seed,
+                    block_len,
+                    fingerprints,
+                    build_hasher,
+                    _t: PhantomData,
+                };
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Restore an xor filter of `m` bits of fingerprints of `bits` bits from the bytes
+    /// returned by [`XorFilter::to_bytes`].
+    /// Returns `None` unless each of the three blocks has a slot at least.
+    pub fn from_bytes(bits: u32, m: u64, bytes: &[u8]) -> Option<Self> {
+        let block_bits = 3 * bits as u64;
+        if bits == 0 || m < block_bits || m % block_bits != 0 || bytes.len() < 8 {
+            return None;
+        }
+        let len = (m / bits as u64) as usize;
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&bytes[..8]);
+        Some(Self {
+            seed: u64::from_le_bytes(seed),
+            block_len: len / 3,
+            fingerprints: Fingerprints::from_bytes(bits, len, &bytes[8..])?,
+            build_hasher: FxBuildHasher::default(),
+            _t: PhantomData,
+        })
+    }
+}
+
+impl<T, B> XorFilter<T, B>
+where
+    T: Hash,
+    B: BuildHasher,
+{
+    /// Number of bits of a fingerprint
+    pub fn bits(&self) -> u32 {
+        self.fingerprints.bits()
+    }
+
+    /// Number of bits of the fingerprints
+    pub fn m(&self) -> usize {
+        self.fingerprints.len() * self.bits() as usize
+    }
+
+    /// The seed followed by the fingerprints
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.seed.to_le_bytes().to_vec();
+        bytes.extend(self.fingerprints.to_bytes());
+        bytes
+    }
+
+    /// False positive rate, which does not depend on the number of items
+    pub fn false_positive_rate(&self) -> f64 {
+        0.5f64.powi(self.bits() as i32)
+    }
+
+    pub fn contains<V>(&self, value: V) -> bool
+    where
+        V: Borrow<T>,
+    {
+        let h = mix(hash64(&self.build_hasher, value.borrow()) ^ self.seed);
+        let [a, b, c] = slots(h, self.block_len);
+        let f = &self.fingerprints;
+        fingerprint(h) & f.mask() == f.get(a) ^ f.get(b) ^ f.get(c)
+    }
+}
+
+/// Assigns fingerprints so that those of the three slots of each hash xor to its fingerprint.
+/// Returns `None` if the hashes cannot be peeled with `seed`.
+fn construct(hashes: &[u64], seed: u64, block_len: usize, bits: u32) -> Option<Fingerprints> {
+    let len = block_len * 3;
+    let mut counts = vec![0u32; len];
+    // xor of the hashes mapped to each slot, which is the hash itself when the count is 1
+    let mut xors = vec![0u64; len];
+    for &hash in hashes {
+        let h = mix(hash ^ seed);
+        for &slot in slots(h, block_len).iter() {
+            counts[slot] += 1;
+            xors[slot] ^= h;
+        }
+    }
+
+    let mut queue = (0..len).filter(|&i| counts[i] == 1).collect::<Vec<_>>();
+    let mut stack = Vec::with_capacity(hashes.len());
+    while let Some(i) = queue.pop() {
+        if counts[i] != 1 {
+            continue;
+        }
+        let h = xors[i];
+        stack.push((h, i));
+        for &slot in slots(h, block_len).iter() {
+            counts[slot] -= 1;
+            xors[slot] ^= h;
+            if counts[slot] == 1 {
+                queue.push(slot);
+            }
+        }
+    }
+    if stack.len() != hashes.len() {
+        return None;
+    }
+
+    let mut fingerprints = Fingerprints::new(bits, len);
+    for (h, i) in stack.into_iter().rev() {
+        // the slot `i` itself is still 0
+        let [a, b, c] = slots(h, block_len);
+        let f = fingerprint(h) ^ fingerprints.get(a) ^ fingerprints.get(b) ^ fingerprints.get(c);
+        fingerprints.set(i, f);
+    }
+    Some(fingerprints)
+}
+
+fn slots(h: u64, block_len: usize) -> [usize; 3] {
+    [
+        reduce(h as u32, block_len),
+        block_len + reduce(h.rotate_left(21) as u32, block_len),
+        2 * block_len + reduce(h.rotate_left(42) as u32, block_len),
+    ]
+}
+
+fn fingerprint(h: u64) -> u64 {
+    h ^ (h >> 32)
+}
+
+/// Maps `x` to `0..n` fairly without a division
+fn reduce(x: u32, n: usize) -> usize {
+    ((x as u64 * n as u64) >> 32) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let filter = XorFilter::<u64>::build::<u64>(&[], 8);
+        assert!(!filter.contains(&10));
+    }
+
+    #[test]
+    fn same() {
+        let values = (0..10000).collect::<Vec<u64>>();
+        let filter = XorFilter::<u64>::build(&values, 8);
+        assert!(values.iter().all(|i| filter.contains(i)));
+    }
+
+    #[test]
+    fn duplicates() {
+        let values = vec![1u64, 2, 2, 3, 3, 3];
+        let filter = XorFilter::<u64>::build(&values, 8);
+        assert!(values.iter().all(|i| filter.contains(i)));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let n = 10000;
+        let values = (0..n).collect::<Vec<u64>>();
+        let filter = XorFilter::<u64>::build(&values, 8);
+        let positives = (n..n * 11).filter(|i| filter.contains(i)).count();
+        let actual = positives as f64 / (n * 10) as f64;
+        let expected = filter.false_positive_rate();
+        assert!(
+            (actual - expected).abs() < expected * 0.3,
+            "actual={}, expected={}",
+            actual,
+            expected
+        );
+        // about 1.23 * 8 bits per item
+        assert!(filter.m() < n as usize * 10, "m={}", filter.m());
+    }
+
+    #[test]
+    fn bytes() {
+        let values = (0..100).collect::<Vec<u64>>();
+        let filter = XorFilter::<u64>::build(&values, 12);
+        let restored =
+            XorFilter::<u64>::from_bytes(filter.bits(), filter.m() as u64, &filter.to_bytes())
+                .unwrap();
+        assert_eq!(filter.to_bytes(), restored.to_bytes());
+        assert!(values.iter().all(|i| restored.contains(i)));
+        assert!(XorFilter::<u64>::from_bytes(12, filter.m() as u64, &[0; 8]).is_none());
+        // no slots to look up
+        assert!(XorFilter::<u64>::from_bytes(12, 0, &[0; 8]).is_none());
+    }
+}
@@ -1,7 +1,253 @@
+use crate::sstable::{FilterKind, FilterSize, PrefixExtractor};
+use beatrice_proto::configuration;
 use repc::configuration::Configuration as RepcConfiguration;
 use serde::{Deserialize, Serialize};
+use std::{error, fmt, net::SocketAddr, path::Path};
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Configuration {
     pub repc: RepcConfiguration,
+    #[serde(default)]
+    pub metrics: Option<MetricsConfiguration>,
+    #[serde(default)]
+    pub admin: Option<AdminConfiguration>,
+    #[serde(default)]
+    pub storage: StorageConfiguration,
+}
+
+impl Configuration {
+    /// Loads the configuration from the YAML file, then overrides it with the `BEATRICE_*` variables in `env`
+    /// and finally with `overrides` such as `storage.memtable_rows=4096`.
+    /// Values are parsed as YAML, so `compaction={full: {tables: 8}}` sets a mapping.
+    pub fn load<I>(file: Option<&Path>, env: I, overrides: &[String]) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        configuration::load(file, env, overrides)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigurationError> {
+        self.storage.validate()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MetricsConfiguration {
+    /// Address to serve the Prometheus metrics endpoint on
+    pub addr: SocketAddr,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AdminConfiguration {
+    /// Address to serve the admin service on
+    pub addr: SocketAddr,
+}
+
+/// Maximum number of levels of the skip list of the memtable, which fits 2^32 rows
+const MAX_MEMTABLE_LEVEL: usize = 32;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfiguration {
+    /// Expected number of rows in the memtable, which sizes its skip list and negative cache
+    pub memtable_rows: usize,
+    /// Number of levels of the skip list of the memtable, overriding the one derived from `memtable_rows`
+    pub memtable_level: Option<usize>,
+    /// Size of the memtable in bytes that triggers a flush on writes
+    pub memtable_size: Option<usize>,
+    /// When to merge the tables
+    pub compaction: CompactionStrategy,
+    /// Milliseconds for which compaction keeps overwritten and deleted versions,
+    /// beyond which reads at past timestamps may miss them. Kept forever if unset.
+    pub retention: Option<u64>,
+    /// Kind of the filter of new SSTables
+    pub filter: FilterKind,
+    /// Target size of the filters of new SSTables,
+    /// e.g. `{false_positive_rate: 0.01}` or `{bits_per_key: 10}`
+    pub filter_size: FilterSize,
+    /// Build no filter for tables produced by compactions
+    pub skip_last_level_filters: bool,
+    /// Extractor of the prefixes of rows inserted into the filters of new SSTables,
+    /// e.g. `{fixed: 4}` or `{delimiter: ":"}`
+    pub prefix_extractor: Option<PrefixExtractor>,
+    // TODO: add data_dir, block_size, compression and cache_size once the store writes
+    // its tables to files and caches their blocks
+}
+
+impl Default for StorageConfiguration {
+    fn default() -> Self {
+        Self {
+            // a skip list of 12 levels
+            memtable_rows: 2048,
+            // derived from memtable_rows
+            memtable_level: None,
+            // flush only on requests
+            memtable_size: None,
+            // compact only on requests to the admin service
+            compaction: CompactionStrategy::Manual,
+            // keep every version
+            retention: None,
+            filter: FilterKind::Bloom,
+            filter_size: FilterSize::FalsePositiveRate(0.001),
+            skip_last_level_filters: false,
+            prefix_extractor: None,
+        }
+    }
+}
+
+impl StorageConfiguration {
+    pub fn validate(&self) -> Result<(), ConfigurationError> {
+        let error = |field, reason: &str| {
+            Err(ConfigurationError {
+                field,
+                reason: reason.to_string(),
+            })
+        };
+        if self.memtable_rows == 0 {
+            return error("storage.memtable_rows", "must be positive");
+        }
+        if let Some(level) = self.memtable_level {
+            if !(1..=MAX_MEMTABLE_LEVEL).contains(&level) {
+                return error(
+                    "storage.memtable_level",
+                    &format!("must be between 1 and {}", MAX_MEMTABLE_LEVEL),
+                );
+            }
+        }
+        if self.memtable_size == Some(0) {
+            return error("storage.memtable_size", "must be positive");
+        }
+        if let CompactionStrategy::Full { tables } = self.compaction {
+            if tables < 2 {
+                return error("storage.compaction.full.tables", "must be at least 2");
+            }
+        }
+        match self.filter_size {
+            FilterSize::FalsePositiveRate(p) if !(p > 0.0 && p < 1.0) => {
+                return error(
+                    "storage.filter_size.false_positive_rate",
+                    "must be between 0 and 1 exclusive",
+                );
+            }
+            FilterSize::BitsPerKey(bits) if !(bits > 0.0 && bits.is_finite()) => {
+                return error("storage.filter_size.bits_per_key", "must be positive");
+            }
+            _ => {}
+        }
+        if let Some(PrefixExtractor::Fixed(0)) = self.prefix_extractor {
+            return error("storage.prefix_extractor.fixed", "must be positive");
+        }
+        Ok(())
+    }
+}
+
+/// When to merge the tables of the store into one
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionStrategy {
+    /// Merge all the tables when a flush makes `tables` tables
+    Full { tables: usize },
+    /// Merge only on requests to the admin service
+    Manual,
+}
+
+impl CompactionStrategy {
+    /// Returns the number of tables that triggers a compaction.
+    pub fn trigger(&self) -> Option<usize> {
+        match self {
+            CompactionStrategy::Full { tables } => Some(*tables),
+            CompactionStrategy::Manual => None,
+        }
+    }
+}
+
+/// An invalid value of a field of the configuration
+#[derive(Debug, PartialEq)]
+pub struct ConfigurationError {
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for ConfigurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}: {}", self.field, self.reason)
+    }
+}
+
+impl error::Error for ConfigurationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        let conf = serde_yaml::from_str::<StorageConfiguration>("{}").unwrap();
+        assert_eq!(2048, conf.memtable_rows);
+        assert_eq!(None, conf.memtable_size);
+        assert_eq!(CompactionStrategy::Manual, conf.compaction);
+        assert_eq!(None, conf.memtable_level);
+        assert_eq!(None, conf.retention);
+        assert_eq!(Ok(()), conf.validate());
+    }
+
+    #[test]
+    fn test_parse() {
+        let conf = serde_yaml::from_str::<StorageConfiguration>(
+            "memtable_size: 4096\nmemtable_level: 4\ncompaction: manual\nfilter_size: {bits_per_key: 10}\n",
+        )
+        .unwrap();
+        assert_eq!(Some(4096), conf.memtable_size);
+        assert_eq!(Some(4), conf.memtable_level);
+        assert_eq!(None, conf.compaction.trigger());
+        assert_eq!(Ok(()), conf.validate());
+
+        let conf = serde_yaml::from_str::<StorageConfiguration>("compaction: {full: {tables: 8}}")
+            .unwrap();
+        assert_eq!(Some(8), conf.compaction.trigger());
+
+        let e = serde_yaml::from_str::<StorageConfiguration>("memtable_row: 1").unwrap_err();
+        assert!(e.to_string().contains("unknown field `memtable_row`"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let invalid = |yaml: &str| {
+            serde_yaml::from_str::<StorageConfiguration>(yaml)
+                .unwrap()
+                .validate()
+                .unwrap_err()
+                .field
+        };
+        assert_eq!("storage.memtable_rows", invalid("memtable_rows: 0"));
+        assert_eq!("storage.memtable_size", invalid("memtable_size: 0"));
+        assert_eq!("storage.memtable_level", invalid("memtable_level: 0"));
+        assert_eq!("storage.memtable_level", invalid("memtable_level: 33"));
+        assert_eq!(
+            "storage.compaction.full.tables",
+            invalid("compaction: {full: {tables: 1}}")
+        );
+        assert_eq!(
+            "storage.filter_size.false_positive_rate",
+            invalid("filter_size: {false_positive_rate: 1.0}")
+        );
+        assert_eq!(
+            "storage.filter_size.bits_per_key",
+            invalid("filter_size: {bits_per_key: -1}")
+        );
+        assert_eq!(
+            "storage.prefix_extractor.fixed",
+            invalid("prefix_extractor: {fixed: 0}")
+        );
+        assert_eq!(
+            "invalid storage.memtable_rows: must be positive",
+            StorageConfiguration {
+                memtable_rows: 0,
+                ..StorageConfiguration::default()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        );
+    }
 }
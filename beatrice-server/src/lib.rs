@@ -1,33 +1,158 @@
+pub mod admin;
 mod collections;
 pub mod configuration;
-mod model;
+pub mod metrics;
+pub mod model;
 mod store;
+mod varint;
 
-use self::{model::Row, store::Store};
+pub use self::store::sstable;
+use self::{
+    admin::AdminService,
+    configuration::StorageConfiguration,
+    metrics::Metrics,
+    model::Row,
+    sstable::{file, TableOptions},
+    store::Store,
+};
 pub use beatrice_proto::beatrice::beatrice_server::BeatriceStateMachine;
 use beatrice_proto::beatrice::{
-    beatrice_server::Beatrice, DeleteRequest, DeleteResponse, FlushRequest, FlushResponse,
-    GetRequest, GetResponse, PutRequest, PutResponse,
+    beatrice_server::Beatrice, mutation::Mutation, BatchRequest, BatchResponse, DeleteRangeRequest,
+    DeleteRangeResponse, DeleteRequest, DeleteResponse, Entry, FlushRequest, FlushResponse,
+    GetRequest, GetResponse, HistoryRequest, HistoryResponse, IngestRequest, IngestResponse,
+    PutRequest, PutResponse, ScanRequest, ScanResponse, Version,
 };
 use bytes::Bytes;
-use std::{convert::TryFrom, time::SystemTime};
-use tonic::{Response, Status};
+use std::{
+    convert::TryFrom,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Instant, SystemTime},
+};
+use tonic::{Code, Response, Status};
 
 pub struct BeatriceState {
-    store: Store,
+    store: Arc<Mutex<Store>>,
+    metrics: Arc<Metrics>,
 }
 
 impl BeatriceState {
     pub fn new() -> Self {
+        Self::with_storage(&StorageConfiguration::default())
+    }
+
+    pub fn with_storage(conf: &StorageConfiguration) -> Self {
+        let store = Store::new(conf.memtable_rows)
+            .with_memtable_level(conf.memtable_level)
+            .with_options(TableOptions {
+                filter: conf.filter,
+                filter_size: conf.filter_size,
+                prefix_extractor: conf.prefix_extractor,
+                skip_last_level_filters: conf.skip_last_level_filters,
+            })
+            .with_memtable_size(conf.memtable_size)
+            .with_compaction_trigger(conf.compaction.trigger())
+            .with_retention(conf.retention);
+        let metrics = Arc::new(Metrics::new(store.metrics()));
         Self {
-            store: Store::new(2048),
+            store: Arc::new(Mutex::new(store)),
+            metrics,
         }
     }
+
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    pub fn admin(&self) -> AdminService {
+        AdminService::new(self.store.clone())
+    }
+
+    fn store(&self) -> Result<MutexGuard<Store>, Status> {
+        self.store
+            .lock()
+            .map_err(|_| Status::internal("store is poisoned"))
+    }
+
+    fn observe<T>(&self, method: &'static str, start: Instant, res: &Result<T, Status>) {
+        let code = match res {
+            Ok(_) => Code::Ok,
+            Err(status) => status.code(),
+        };
+        self.metrics.observe_rpc(method, start.elapsed(), code);
+    }
 }
 
 #[repc::async_trait]
 impl Beatrice for BeatriceState {
     async fn put(&mut self, req: PutRequest) -> Result<Response<PutResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_put(req);
+        self.observe("put", start, &res);
+        res
+    }
+
+    async fn get(&mut self, req: GetRequest) -> Result<Response<GetResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_get(req);
+        self.observe("get", start, &res);
+        res
+    }
+
+    async fn delete(&mut self, req: DeleteRequest) -> Result<Response<DeleteResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_delete(req);
+        self.observe("delete", start, &res);
+        res
+    }
+
+    async fn delete_range(
+        &mut self,
+        req: DeleteRangeRequest,
+    ) -> Result<Response<DeleteRangeResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_delete_range(req);
+        self.observe("delete_range", start, &res);
+        res
+    }
+
+    async fn flush(&mut self, req: FlushRequest) -> Result<Response<FlushResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_flush(req);
+        self.observe("flush", start, &res);
+        res
+    }
+
+    async fn scan(&mut self, req: ScanRequest) -> Result<Response<ScanResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_scan(req);
+        self.observe("scan", start, &res);
+        res
+    }
+
+    async fn batch(&mut self, req: BatchRequest) -> Result<Response<BatchResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_batch(req);
+        self.observe("batch", start, &res);
+        res
+    }
+
+    async fn history(&mut self, req: HistoryRequest) -> Result<Response<HistoryResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_history(req);
+        self.observe("history", start, &res);
+        res
+    }
+
+    async fn ingest(&mut self, req: IngestRequest) -> Result<Response<IngestResponse>, Status> {
+        let start = Instant::now();
+        let res = self.handle_ingest(req);
+        self.observe("ingest", start, &res);
+        res
+    }
+}
+
+impl BeatriceState {
+    fn handle_put(&mut self, req: PutRequest) -> Result<Response<PutResponse>, Status> {
         let row = Row::new(Bytes::from(req.row));
         let timestamp = match req.timestamp {
             0 => get_current_timestamp_millis().map_err(|e| {
@@ -36,15 +161,19 @@ impl Beatrice for BeatriceState {
             t => t,
         };
         let val = Bytes::from(req.value);
-        self.store.put(row, timestamp, val);
+        self.store()?.put(row, timestamp, val);
 
         Ok(Response::new(PutResponse {}))
     }
 
-    async fn get(&mut self, req: GetRequest) -> Result<Response<GetResponse>, Status> {
+    fn handle_get(&mut self, req: GetRequest) -> Result<Response<GetResponse>, Status> {
         let row = Row::new(Bytes::from(req.row));
-        self.store
-            .get_latest(&row)
+        let store = self.store()?;
+        let latest = match req.timestamp {
+            0 => store.get_latest(&row),
+            t => store.get_at(&row, t),
+        };
+        latest
             .map(|(k, v)| {
                 Response::new(GetResponse {
                     timestamp: k.timestamp(),
@@ -54,7 +183,7 @@ impl Beatrice for BeatriceState {
             .ok_or_else(|| Status::not_found("not found"))
     }
 
-    async fn delete(&mut self, req: DeleteRequest) -> Result<Response<DeleteResponse>, Status> {
+    fn handle_delete(&mut self, req: DeleteRequest) -> Result<Response<DeleteResponse>, Status> {
         let row = Row::new(Bytes::from(req.row));
         let timestamp = match req.timestamp {
             0 => get_current_timestamp_millis().map_err(|e| {
@@ -62,15 +191,133 @@ impl Beatrice for BeatriceState {
             })?,
             t => t,
         };
-        self.store.delete(row, timestamp);
+        self.store()?.delete(row, timestamp);
 
         Ok(Response::new(DeleteResponse {}))
     }
 
-    async fn flush(&mut self, req: FlushRequest) -> Result<Response<FlushResponse>, Status> {
-        self.store.flush(req.cache);
+    fn handle_delete_range(
+        &mut self,
+        req: DeleteRangeRequest,
+    ) -> Result<Response<DeleteRangeResponse>, Status> {
+        let start = Row::new(Bytes::from(req.start));
+        let end = Row::new(Bytes::from(req.end));
+        if start >= end {
+            return Err(Status::invalid_argument(
+                "start row must be smaller than end row",
+            ));
+        }
+        let timestamp = match req.timestamp {
+            0 => get_current_timestamp_millis().map_err(|e| {
+                Status::internal(format!("failed to get current time: error={:?}", e))
+            })?,
+            t => t,
+        };
+        self.store()?.delete_range(start, end, timestamp);
+
+        Ok(Response::new(DeleteRangeResponse {}))
+    }
+
+    fn handle_flush(&mut self, req: FlushRequest) -> Result<Response<FlushResponse>, Status> {
+        self.store()?.flush(req.cache);
         Ok(Response::new(FlushResponse {}))
     }
+
+    fn handle_scan(&mut self, req: ScanRequest) -> Result<Response<ScanResponse>, Status> {
+        let entries = self
+            .store()?
+            .scan(&req.prefix, &req.start, req.limit as usize)
+            .into_iter()
+            .map(|(k, v)| Entry {
+                row: k.row().get().to_vec(),
+                timestamp: k.timestamp(),
+                value: v.to_vec(),
+            })
+            .collect();
+        Ok(Response::new(ScanResponse { entries }))
+    }
+
+    fn handle_batch(&mut self, req: BatchRequest) -> Result<Response<BatchResponse>, Status> {
+        let now = get_current_timestamp_millis()
+            .map_err(|e| Status::internal(format!("failed to get current time: error={:?}", e)))?;
+        let timestamp = |t| if t == 0 { now } else { t };
+
+        // Validate all the mutations before applying any of them.
+        let mut mutations = Vec::with_capacity(req.mutations.len());
+        for m in req.mutations {
+            match m.mutation {
+                Some(Mutation::DeleteRange(r)) if r.start >= r.end => {
+                    return Err(Status::invalid_argument(
+                        "start row must be smaller than end row",
+                    ));
+                }
+                Some(m) => mutations.push(m),
+                None => return Err(Status::invalid_argument("mutation is empty")),
+            }
+        }
+
+        let mut store = self.store()?;
+        for m in mutations {
+            match m {
+                Mutation::Put(r) => store.put(
+                    Row::new(Bytes::from(r.row)),
+                    timestamp(r.timestamp),
+                    Bytes::from(r.value),
+                ),
+                Mutation::Delete(r) => {
+                    store.delete(Row::new(Bytes::from(r.row)), timestamp(r.timestamp))
+                }
+                Mutation::DeleteRange(r) => store.delete_range(
+                    Row::new(Bytes::from(r.start)),
+                    Row::new(Bytes::from(r.end)),
+                    timestamp(r.timestamp),
+                ),
+            }
+        }
+        Ok(Response::new(BatchResponse {}))
+    }
+
+    fn handle_history(&mut self, req: HistoryRequest) -> Result<Response<HistoryResponse>, Status> {
+        let row = Row::new(Bytes::from(req.row));
+        let limit = match req.limit {
+            0 => usize::max_value(),
+            n => n as usize,
+        };
+        let versions = self
+            .store()?
+            .history(&row, limit)
+            .into_iter()
+            .map(|(timestamp, value)| Version {
+                timestamp,
+                deleted: value.is_none(),
+                value: value.map_or_else(Vec::new, |v| v.to_vec()),
+            })
+            .collect();
+        Ok(Response::new(HistoryResponse { versions }))
+    }
+
+    /// Verifies the checksums and the order of the keys of the file before adding it,
+    /// so that every node either adds the same table or rejects it.
+    fn handle_ingest(&mut self, req: IngestRequest) -> Result<Response<IngestResponse>, Status> {
+        let buf = Bytes::from(req.file);
+        let table = file::decode(buf, true)
+            .and_then(|table| file::verify(&table).map(|_| table))
+            .map_err(|e| Status::invalid_argument(format!("invalid sstable: {}", e)))?;
+
+        let mut store = self.store()?;
+        let id = store.ingest(table.sstable);
+        let table = store
+            .tables()
+            .into_iter()
+            .find(|t| t.id == id)
+            .expect("ingested table is in the store");
+        Ok(Response::new(IngestResponse {
+            entries: table.len as u64,
+            range_tombstones: table.tombstones as u64,
+            first_row: table.first_row.map_or_else(Vec::new, |r| r.get().to_vec()),
+            last_row: table.last_row.map_or_else(Vec::new, |r| r.get().to_vec()),
+        }))
+    }
 }
 
 fn get_current_timestamp_millis() -> anyhow::Result<u64> {
@@ -0,0 +1,330 @@
+use crate::store::StoreMetrics;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt::{self, Write},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tonic::Code;
+
+#[derive(Default, Debug)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub fn set(&self, v: u64) {
+        self.0.store(v, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Upper bounds of the histogram buckets in seconds
+const BOUNDS: [f64; 10] = [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// A histogram of durations with fixed buckets.
+#[derive(Default, Debug)]
+pub struct Histogram {
+    buckets: [AtomicU64; 10],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, d: Duration) {
+        let secs = d.as_secs_f64();
+        if let Some(i) = BOUNDS.iter().position(|&b| secs <= b) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(d.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default, Debug)]
+struct RpcMetrics {
+    /// Number of responses by status code
+    responses: BTreeMap<String, u64>,
+    duration: Histogram,
+}
+
+/// Metrics of the server exported in the Prometheus text format.
+#[derive(Debug)]
+pub struct Metrics {
+    rpcs: Mutex<BTreeMap<&'static str, RpcMetrics>>,
+    store: Arc<StoreMetrics>,
+}
+
+impl Metrics {
+    pub(crate) fn new(store: Arc<StoreMetrics>) -> Self {
+        Self {
+            rpcs: Mutex::default(),
+            store,
+        }
+    }
+
+    pub fn observe_rpc(&self, method: &'static str, d: Duration, code: Code) {
+        let mut rpcs = self.rpcs.lock().unwrap();
+        let rpc = rpcs.entry(method).or_default();
+        *rpc.responses.entry(format!("{:?}", code)).or_default() += 1;
+        rpc.duration.observe(d);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.write_to(&mut Encoder::new(&mut out))
+            .expect("writing to String never fails");
+        out
+    }
+
+    fn write_to(&self, e: &mut Encoder) -> fmt::Result {
+        {
+            let rpcs = self.rpcs.lock().unwrap();
+            e.family(
+                "beatrice_rpc_requests_total",
+                "counter",
+                "Number of handled RPC requests.",
+            )?;
+            for (method, rpc) in rpcs.iter() {
+                for (code, n) in rpc.responses.iter() {
+                    e.sample(&[("method", method), ("code", code)], *n)?;
+                }
+            }
+            e.family(
+                "beatrice_rpc_duration_seconds",
+                "histogram",
+                "Latency of RPC requests.",
+            )?;
+            for (method, rpc) in rpcs.iter() {
+                e.histogram(&[("method", method)], &rpc.duration)?;
+            }
+        }
+
+        let s = &self.store;
+        e.gauge(
+            "beatrice_memtable_bytes",
+            "Size of the memtable in bytes.",
+            s.memtable_bytes.get(),
+        )?;
+        e.gauge(
+            "beatrice_memtable_entries",
+            "Number of entries in the memtable.",
+            s.memtable_entries.get(),
+        )?;
+        e.gauge("beatrice_sstables", "Number of SSTables.", s.sstables.get())?;
+        e.gauge(
+            "beatrice_sstable_bytes",
+            "Total size of SSTable data in bytes.",
+            s.sstable_bytes.get(),
+        )?;
+        e.family(
+            "beatrice_filter_checks_total",
+            "counter",
+            "Number of SSTable filter checks by result.",
+        )?;
+        e.sample(&[("result", "negative")], s.filter_negatives.get())?;
+        e.sample(
+            &[("result", "true_positive")],
+            s.filter_true_positives.get(),
+        )?;
+        e.sample(
+            &[("result", "false_positive")],
+            s.filter_false_positives.get(),
+        )?;
+        e.family(
+            "beatrice_prefix_filter_checks_total",
+            "counter",
+            "Number of SSTable filter checks by prefix scans by result.",
+        )?;
+        e.sample(&[("result", "negative")], s.prefix_filter_negatives.get())?;
+        e.sample(&[("result", "positive")], s.prefix_filter_positives.get())?;
+        e.counter(
+            "beatrice_flushes_total",
+            "Number of memtable flushes.",
+            s.flushes.get(),
+        )?;
+        e.family(
+            "beatrice_flush_duration_seconds",
+            "histogram",
+            "Duration of memtable flushes.",
+        )?;
+        e.histogram(&[], &s.flush_duration)?;
+        e.counter(
+            "beatrice_compactions_total",
+            "Number of SSTable compactions.",
+            s.compactions.get(),
+        )?;
+        e.family(
+            "beatrice_compaction_duration_seconds",
+            "histogram",
+            "Duration of SSTable compactions.",
+        )?;
+        e.histogram(&[], &s.compaction_duration)?;
+        e.family(
+            "beatrice_cache_lookups_total",
+            "counter",
+            "Number of SSTable buffer pool lookups by result.",
+        )?;
+        e.sample(&[("result", "hit")], s.cache_hits.get())?;
+        e.sample(&[("result", "miss")], s.cache_misses.get())?;
+        Ok(())
+    }
+}
+
+struct Encoder<'a> {
+    out: &'a mut String,
+    name: &'static str,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(out: &'a mut String) -> Self {
+        Self { out, name: "" }
+    }
+
+    fn family(&mut self, name: &'static str, ty: &str, help: &str) -> fmt::Result {
+        self.name = name;
+        writeln!(self.out, "# HELP {} {}", name, help)?;
+        writeln!(self.out, "# TYPE {} {}", name, ty)
+    }
+
+    fn counter(&mut self, name: &'static str, help: &str, v: u64) -> fmt::Result {
+        self.family(name, "counter", help)?;
+        self.sample(&[], v)
+    }
+
+    fn gauge(&mut self, name: &'static str, help: &str, v: u64) -> fmt::Result {
+        self.family(name, "gauge", help)?;
+        self.sample(&[], v)
+    }
+
+    fn sample(&mut self, labels: &[(&str, &str)], v: u64) -> fmt::Result {
+        write_sample(self.out, self.name, "", labels, v)
+    }
+
+    fn histogram(&mut self, labels: &[(&str, &str)], h: &Histogram) -> fmt::Result {
+        let mut cumulative = 0;
+        for (bound, bucket) in BOUNDS.iter().zip(h.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = bound.to_string();
+            let mut ls = labels.to_vec();
+            ls.push(("le", &le));
+            write_sample(self.out, self.name, "_bucket", &ls, cumulative)?;
+        }
+        let count = h.count.load(Ordering::Relaxed);
+        let mut ls = labels.to_vec();
+        ls.push(("le", "+Inf"));
+        write_sample(self.out, self.name, "_bucket", &ls, count)?;
+
+        let sum = h.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        write_sample(self.out, self.name, "_sum", labels, sum)?;
+        write_sample(self.out, self.name, "_count", labels, count)
+    }
+}
+
+fn write_sample<V: fmt::Display>(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    labels: &[(&str, &str)],
+    v: V,
+) -> fmt::Result {
+    write!(out, "{}{}", name, suffix)?;
+    if !labels.is_empty() {
+        let labels = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, v))
+            .collect::<Vec<_>>();
+        write!(out, "{{{}}}", labels.join(","))?;
+    }
+    writeln!(out, " {}", v)
+}
+
+/// Serves the metrics on `http://<addr>/metrics`.
+pub async fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let metrics = metrics.clone();
+                async move { Ok::<_, Infallible>(handle(&metrics, req)) }
+            }))
+        }
+    });
+    Server::bind(&addr).serve(make_service).await
+}
+
+fn handle(metrics: &Metrics, req: Request<Body>) -> Response<Body> {
+    let mut res = Response::default();
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => {
+            res.headers_mut()
+                .insert(CONTENT_TYPE, "text/plain; version=0.0.4".parse().unwrap());
+            *res.body_mut() = Body::from(metrics.render());
+        }
+        _ => {
+            *res.status_mut() = StatusCode::NOT_FOUND;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new(Arc::default());
+        metrics.observe_rpc("get", Duration::from_micros(300), Code::Ok);
+        metrics.observe_rpc("get", Duration::from_millis(20), Code::NotFound);
+        metrics.store.sstables.set(2);
+
+        let out = metrics.render();
+        let lines = out.lines().collect::<Vec<_>>();
+        for expected in &[
+            r#"beatrice_rpc_requests_total{method="get",code="Ok"} 1"#,
+            r#"beatrice_rpc_requests_total{method="get",code="NotFound"} 1"#,
+            r#"beatrice_rpc_duration_seconds_bucket{method="get",le="0.0001"} 0"#,
+            r#"beatrice_rpc_duration_seconds_bucket{method="get",le="0.0005"} 1"#,
+            r#"beatrice_rpc_duration_seconds_bucket{method="get",le="0.05"} 2"#,
+            r#"beatrice_rpc_duration_seconds_bucket{method="get",le="+Inf"} 2"#,
+            r#"beatrice_rpc_duration_seconds_sum{method="get"} 0.0203"#,
+            r#"beatrice_rpc_duration_seconds_count{method="get"} 2"#,
+            "# TYPE beatrice_sstables gauge",
+            "beatrice_sstables 2",
+            "beatrice_flush_duration_seconds_count 0",
+        ] {
+            assert!(
+                lines.contains(expected),
+                "missing `{}` in\n{}",
+                expected,
+                out
+            );
+        }
+    }
+}
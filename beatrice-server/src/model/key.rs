@@ -1,4 +1,5 @@
 use super::Row;
+use crate::varint;
 use bytes::{Buf, BufMut, Bytes};
 use std::{cmp::Ordering, mem};
 
@@ -22,21 +23,40 @@ impl Key {
     }
 
     pub fn size(&self) -> usize {
-        mem::size_of_val(&self.timestamp) + mem::size_of::<u64>() + self.row.0.len()
+        varint::size(self.timestamp) + varint::size(self.row.0.len() as u64) + self.row.0.len()
     }
 
-    pub fn read_from(buf: &mut Bytes) -> Self {
+    /// Reads a key from `buf`.
+    /// Returns `None` if `buf` is corrupted.
+    pub fn read_from(buf: &mut Bytes) -> Option<Self> {
+        let timestamp = varint::get(buf)?;
+        let len = varint::get(buf)? as usize;
+        if len > buf.remaining() {
+            return None;
+        }
+        let row = Row::new(buf.split_to(len));
+        Some(Key { row, timestamp })
+    }
+
+    /// Reads a key written with fixed-size `u64` timestamp and length,
+    /// the encoding of SSTable format versions 1 and 2.
+    pub fn read_from_v0(buf: &mut Bytes) -> Option<Self> {
+        if buf.remaining() < 2 * mem::size_of::<u64>() {
+            return None;
+        }
         let timestamp = buf.get_u64_le();
         let len = buf.get_u64_le() as usize;
-        let row = Row::new(buf.slice(..len));
-        buf.advance(len);
-        Key { row, timestamp }
+        if len > buf.remaining() {
+            return None;
+        }
+        let row = Row::new(buf.split_to(len));
+        Some(Key { row, timestamp })
     }
 
     pub fn write_to<T: BufMut>(self, buf: &mut T) -> usize {
         let size = self.size();
-        buf.put_u64_le(self.timestamp);
-        buf.put_u64_le(self.row.0.len() as u64);
+        varint::put(buf, self.timestamp);
+        varint::put(buf, self.row.0.len() as u64);
         buf.put(self.row.0);
         size
     }
@@ -67,9 +87,24 @@ mod tests {
         key.clone().write_to(&mut buf);
 
         let mut buf = buf.freeze();
-        let k = Key::read_from(&mut buf);
+        let k = Key::read_from(&mut buf).unwrap();
 
         assert_eq!(key, k);
         assert_eq!(buf.remaining(), 0);
     }
+
+    #[test]
+    fn test_read_v0() {
+        let mut buf = BytesMut::new();
+        buf.put_u64_le(100);
+        buf.put_u64_le(3);
+        buf.put_slice(b"row");
+
+        let mut buf = buf.freeze();
+        let k = Key::read_from_v0(&mut buf).unwrap();
+
+        assert_eq!(Key::new(Row::new(Bytes::from("row")), 100), k);
+        assert_eq!(buf.remaining(), 0);
+        assert_eq!(None, Key::read_from_v0(&mut Bytes::from(vec![0; 12])));
+    }
 }
@@ -1,7 +1,8 @@
 mod key;
+mod tombstone;
 mod value;
 
-pub use self::{key::Key, value::Value};
+pub use self::{key::Key, tombstone::RangeTombstone, value::Value};
 use bytes::Bytes;
 
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -0,0 +1,60 @@
+use super::Row;
+use std::mem;
+
+/// A tombstone that deletes every version of the rows in `[start, end)`
+/// whose timestamp is less than or equal to `timestamp`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RangeTombstone {
+    start: Row,
+    end: Row,
+    timestamp: u64,
+}
+
+impl RangeTombstone {
+    pub fn new(start: Row, end: Row, timestamp: u64) -> Self {
+        debug_assert!(start < end);
+        Self {
+            start,
+            end,
+            timestamp,
+        }
+    }
+
+    pub fn start(&self) -> &Row {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Row {
+        &self.end
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Size of the rows and the timestamp in bytes
+    pub fn size(&self) -> usize {
+        self.start.get().len() + self.end.get().len() + mem::size_of::<u64>()
+    }
+
+    pub fn contains(&self, row: &Row) -> bool {
+        &self.start <= row && row < &self.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_contains() {
+        let tombstone =
+            RangeTombstone::new(Row::new(Bytes::from("b")), Row::new(Bytes::from("d")), 100);
+        assert!(!tombstone.contains(&Row::new(Bytes::from("a"))));
+        assert!(tombstone.contains(&Row::new(Bytes::from("b"))));
+        assert!(tombstone.contains(&Row::new(Bytes::from("c"))));
+        assert!(tombstone.contains(&Row::new(Bytes::from("cz"))));
+        assert!(!tombstone.contains(&Row::new(Bytes::from("d"))));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::varint;
 use bytes::{Buf, BufMut, Bytes};
 use std::mem;
 
@@ -13,7 +14,7 @@ impl Value {
 
     pub fn size(&self) -> usize {
         match self {
-            Value::Val(v) => mem::size_of::<u8>() + mem::size_of::<u64>() + v.len(),
+            Value::Val(v) => mem::size_of::<u8>() + varint::size(v.len() as u64) + v.len(),
             Value::Del => mem::size_of::<u8>(),
         }
     }
@@ -23,7 +24,7 @@ impl Value {
         match self {
             Value::Val(v) => {
                 buf.put_u8(Self::VAL);
-                buf.put_u64_le(v.len() as u64);
+                varint::put(buf, v.len() as u64);
                 buf.put(v);
             }
             Value::Del => {
@@ -33,18 +34,41 @@ impl Value {
         size
     }
 
-    pub fn read_from(buf: &mut Bytes) -> Self {
-        // TODO: detect corruption
-        let marker = buf.get_u8();
-        match marker {
-            Self::VAL => {
-                let len = buf.get_u64_le() as usize;
-                let val = buf.slice(..len);
-                buf.advance(len);
+    /// Reads a value from `buf`.
+    /// Returns `None` if `buf` is corrupted.
+    pub fn read_from(buf: &mut Bytes) -> Option<Self> {
+        Self::read_with(buf, varint::get)
+    }
 
-                Self::Val(val)
+    /// Reads a value written with a fixed-size `u64` length prefix,
+    /// the encoding of SSTable format versions 1 to 3.
+    pub fn read_from_v1(buf: &mut Bytes) -> Option<Self> {
+        Self::read_with(buf, |buf| {
+            if buf.remaining() < mem::size_of::<u64>() {
+                None
+            } else {
+                Some(buf.get_u64_le())
             }
-            _ => Self::Del,
+        })
+    }
+
+    fn read_with<F>(buf: &mut Bytes, read_len: F) -> Option<Self>
+    where
+        F: FnOnce(&mut Bytes) -> Option<u64>,
+    {
+        if !buf.has_remaining() {
+            return None;
+        }
+        match buf.get_u8() {
+            Self::VAL => {
+                let len = read_len(buf)? as usize;
+                if len > buf.remaining() {
+                    return None;
+                }
+                Some(Self::Val(buf.split_to(len)))
+            }
+            Self::DEL => Some(Self::Del),
+            _ => None,
         }
     }
 }
@@ -60,7 +84,7 @@ mod tests {
         value.clone().write_to(&mut buf);
 
         let mut buf = buf.freeze();
-        let v = Value::read_from(&mut buf);
+        let v = Value::read_from(&mut buf).unwrap();
 
         assert_eq!(value, v);
         assert_eq!(buf.remaining(), 0);
@@ -73,9 +97,33 @@ mod tests {
         value.clone().write_to(&mut buf);
 
         let mut buf = buf.freeze();
-        let v = Value::read_from(&mut buf);
+        let v = Value::read_from(&mut buf).unwrap();
 
         assert_eq!(value, v);
         assert_eq!(buf.remaining(), 0);
     }
+
+    #[test]
+    fn test_read_v1() {
+        let mut buf = BytesMut::new();
+        buf.put_u8(Value::VAL);
+        buf.put_u64_le(3);
+        buf.put_slice(b"abc");
+
+        let mut buf = buf.freeze();
+        let v = Value::read_from_v1(&mut buf).unwrap();
+
+        assert_eq!(Value::Val(Bytes::from("abc")), v);
+        assert_eq!(buf.remaining(), 0);
+    }
+
+    #[test]
+    fn test_read_corrupted() {
+        assert_eq!(None, Value::read_from(&mut Bytes::from_static(&[2])));
+        assert_eq!(
+            None,
+            Value::read_from(&mut Bytes::from_static(&[0, 5, b'a']))
+        );
+        assert_eq!(None, Value::read_from(&mut Bytes::new()));
+    }
 }
@@ -0,0 +1,28 @@
+use crate::metrics::{Counter, Gauge, Histogram};
+
+#[derive(Default, Debug)]
+pub struct StoreMetrics {
+    pub memtable_bytes: Gauge,
+    pub memtable_entries: Gauge,
+    pub sstables: Gauge,
+    pub sstable_bytes: Gauge,
+
+    /// Filter checks that rejected a row
+    pub filter_negatives: Counter,
+    /// Filter checks that passed and the row was found in the table
+    pub filter_true_positives: Counter,
+    /// Filter checks that passed but the row was not found in the table
+    pub filter_false_positives: Counter,
+    /// Prefix scans that skipped a table by its filter
+    pub prefix_filter_negatives: Counter,
+    /// Prefix scans that read a table
+    pub prefix_filter_positives: Counter,
+
+    pub flushes: Counter,
+    pub flush_duration: Histogram,
+    pub compactions: Counter,
+    pub compaction_duration: Histogram,
+
+    pub cache_hits: Counter,
+    pub cache_misses: Counter,
+}
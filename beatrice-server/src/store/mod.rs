@@ -1,33 +1,122 @@
-mod compacter;
+mod metrics;
 mod persistent;
-mod sstable;
+pub mod sstable;
 mod stat;
+mod tombstone;
 mod volatile;
 
-use self::{persistent::PersistentStore, volatile::VolatileStore};
-use crate::model::{Key, Row, Value};
+pub use self::{
+    metrics::StoreMetrics,
+    persistent::{TableId, TableInfo},
+    stat::Stat,
+};
+use self::{
+    persistent::PersistentStore,
+    sstable::{SSTable, TableOptions},
+    volatile::VolatileStore,
+};
+use crate::model::{Key, RangeTombstone, Row, Value};
 use bytes::Bytes;
+use std::{
+    cmp,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+/// A version of a row stored in the memtable or in an SSTable.
+pub struct Version {
+    /// `None` if the version is in the memtable
+    pub table_id: Option<TableId>,
+    pub timestamp: u64,
+    /// `None` if the version is a range tombstone
+    pub value: Option<Value>,
+}
 
 pub struct Store {
     volatile: VolatileStore,
     persistent: PersistentStore,
+    options: TableOptions,
+    /// Size of the memtable in bytes that triggers a flush
+    memtable_size: Option<usize>,
+    /// Number of tables that triggers a compaction after flush
+    compaction_trigger: Option<usize>,
+    /// Milliseconds for which compaction keeps old and deleted versions
+    retention: Option<u64>,
+    metrics: Arc<StoreMetrics>,
 }
 
 impl Store {
+    /// Creates a store whose memtable is sized for `n` rows.
     pub fn new(n: usize) -> Self {
+        let metrics = Arc::new(StoreMetrics::default());
         Self {
             volatile: VolatileStore::new(n),
-            persistent: PersistentStore::default(),
+            persistent: PersistentStore::new(metrics.clone()),
+            options: TableOptions::default(),
+            memtable_size: None,
+            compaction_trigger: None,
+            retention: None,
+            metrics,
+        }
+    }
+
+    /// Sets the number of levels of the skip list of the memtable if `level` is given,
+    /// instead of deriving it from the number of rows.
+    pub fn with_memtable_level(mut self, level: Option<usize>) -> Self {
+        if let Some(level) = level {
+            self.volatile = self.volatile.with_level(level);
         }
+        self
+    }
+
+    /// Flushes the memtable on writes once its size reaches `size` bytes.
+    /// The memtable is flushed only on requests if `size` is `None`.
+    pub fn with_memtable_size(mut self, size: Option<usize>) -> Self {
+        self.memtable_size = size;
+        self
+    }
+
+    /// Merges all the tables into one when a flush makes `tables` tables.
+    /// Tables are compacted only on requests if `tables` is `None`.
+    pub fn with_compaction_trigger(mut self, tables: Option<usize>) -> Self {
+        self.compaction_trigger = tables;
+        self
+    }
+
+    /// Lets compaction drop the versions and the range tombstones which only reads at
+    /// timestamps older than `retention` milliseconds before the compaction can see.
+    /// All of them are kept if `retention` is `None`.
+    pub fn with_retention(mut self, retention: Option<u64>) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Sets the options of new tables.
+    pub fn with_options(mut self, options: TableOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn metrics(&self) -> Arc<StoreMetrics> {
+        self.metrics.clone()
     }
 
     pub fn get_latest(&self, row: &Row) -> Option<(Key, Bytes)> {
-        let volatile = self.volatile.get_latest(row);
-        let persistent = self.persistent.get_latest(row);
+        self.get_at(row, u64::max_value())
+    }
+
+    /// Returns the latest version of `row` written at or before `timestamp`
+    /// unless it is deleted at or before `timestamp`.
+    ///
+    /// A key written more than once resolves to the last write:
+    /// the memtable wins over the tables, and a newer table over an older one.
+    pub fn get_at(&self, row: &Row, timestamp: u64) -> Option<(Key, Bytes)> {
+        let volatile = self.volatile.get_at(row, timestamp);
+        let persistent = self.persistent.get_at(row, timestamp);
 
         let kv = match (volatile, persistent) {
             (Some((k1, v1)), Some((k2, v2))) => {
-                if k1.timestamp() > k2.timestamp() {
+                if k1.timestamp() >= k2.timestamp() {
                     Some((k1.clone(), v1.clone()))
                 } else {
                     Some((k2, v2))
@@ -37,33 +126,215 @@ impl Store {
             (None, Some(kv2)) => Some(kv2),
             (None, None) => None,
         };
-        kv.and_then(|(k, v)| {
-            if let Value::Val(v) = v {
-                Some((k, v))
-            } else {
-                None
-            }
+        let deleted_at = cmp::max(
+            self.volatile.get_latest_tombstone_at(row, timestamp),
+            self.persistent.get_latest_tombstone_at(row, timestamp),
+        );
+        kv.and_then(|(k, v)| match v {
+            Value::Val(v) if deleted_at.map_or(true, |t| k.timestamp() > t) => Some((k, v)),
+            _ => None,
         })
     }
 
+    /// Returns the latest value of up to `limit` rows starting with `prefix`
+    /// from `start` in order, or of all of them if `limit` is 0.
+    pub fn scan(&self, prefix: &[u8], start: &[u8], limit: usize) -> Vec<(Key, Bytes)> {
+        let limit = if limit == 0 {
+            usize::max_value()
+        } else {
+            limit
+        };
+        let mut latest = self.persistent.scan_prefix(prefix, start);
+        let mut last_row = None;
+        // versions of each row come from the latest
+        for (k, v) in self.volatile.scan_prefix(prefix, start) {
+            if last_row == Some(k.row()) {
+                continue;
+            }
+            last_row = Some(k.row());
+            match latest.get(k.row()) {
+                Some((key, _)) if key.timestamp() > k.timestamp() => {}
+                _ => {
+                    latest.insert(k.row().clone(), (k.clone(), v.clone()));
+                }
+            }
+        }
+        latest
+            .into_iter()
+            .filter_map(|(row, (k, v))| {
+                let deleted_at = cmp::max(
+                    self.volatile.get_latest_tombstone(&row),
+                    self.persistent.get_latest_tombstone(&row),
+                );
+                match v {
+                    Value::Val(v) if deleted_at.map_or(true, |t| k.timestamp() > t) => Some((k, v)),
+                    _ => None,
+                }
+            })
+            .take(limit)
+            .collect()
+    }
+
     pub fn put(&mut self, row: Row, timestamp: u64, val: Bytes) {
         self.volatile.insert(row, timestamp, Value::Val(val));
+        self.update_memtable_metrics();
+        self.flush_if_full();
     }
 
     pub fn delete(&mut self, row: Row, timestamp: u64) {
         self.volatile.insert(row, timestamp, Value::Del);
+        self.update_memtable_metrics();
+        self.flush_if_full();
+    }
+
+    /// Deletes all the rows in `[start, end)` written at or before `timestamp`.
+    pub fn delete_range(&mut self, start: Row, end: Row, timestamp: u64) {
+        self.volatile
+            .insert_tombstone(RangeTombstone::new(start, end, timestamp));
+        self.update_memtable_metrics();
+        self.flush_if_full();
     }
 
     pub fn flush(&mut self, cache: bool) {
-        let sstable = self.volatile.flush();
+        if self.volatile.is_empty() {
+            return;
+        }
+        let start = Instant::now();
+        let sstable = self.volatile.flush(&self.options);
         self.persistent.add(sstable, cache);
         self.volatile.clear();
+        self.metrics.flushes.inc();
+        self.metrics.flush_duration.observe(start.elapsed());
+        self.update_memtable_metrics();
+
+        if let Some(trigger) = self.compaction_trigger {
+            if self.persistent.num_tables() >= trigger {
+                self.compact_tables();
+            }
+        }
+    }
+
+    fn flush_if_full(&mut self) {
+        if let Some(size) = self.memtable_size {
+            if self.volatile.size() >= size {
+                self.flush(true);
+            }
+        }
+    }
+
+    /// Adds a table built outside the store, which should be verified by
+    /// [`sstable::file::verify`] beforehand.
+    pub fn ingest(&mut self, sstable: SSTable) -> TableId {
+        self.persistent.add(sstable, true)
+    }
+
+    /// Merges all the tables into one, leaving the memtable alone.
+    pub fn compact(&mut self) {
+        self.compact_tables();
+    }
+
+    fn compact_tables(&mut self) {
+        let start = Instant::now();
+        self.persistent.compact(&self.options, self.gc_horizon());
+        self.metrics.compactions.inc();
+        self.metrics.compaction_duration.observe(start.elapsed());
+    }
+
+    /// The oldest timestamp at which reads have to be preserved by compaction
+    fn gc_horizon(&self) -> Option<u64> {
+        let retention = self.retention?;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .ok()?;
+        Some((now.as_millis() as u64).saturating_sub(retention))
+    }
+
+    pub fn memtable_stat(&self) -> (&Stat, usize) {
+        (self.volatile.stat(), self.volatile.tombstones().len())
+    }
+
+    pub fn tables(&self) -> Vec<TableInfo> {
+        self.persistent.tables()
+    }
+
+    /// Returns all the versions of `row` including deleted ones and range tombstones.
+    pub fn dump(&self, row: &Row) -> Vec<Version> {
+        let mut versions = vec![];
+        for (k, v) in self.volatile.get_versions(row) {
+            versions.push(Version {
+                table_id: None,
+                timestamp: k.timestamp(),
+                value: Some(v.clone()),
+            });
+        }
+        for t in self
+            .volatile
+            .tombstones()
+            .iter()
+            .filter(|t| t.contains(row))
+        {
+            versions.push(Version {
+                table_id: None,
+                timestamp: t.timestamp(),
+                value: None,
+            });
+        }
+        for (id, k, v) in self.persistent.get_versions(row) {
+            versions.push(Version {
+                table_id: Some(id),
+                timestamp: k.timestamp(),
+                value: Some(v),
+            });
+        }
+        for (id, timestamp) in self.persistent.get_tombstones(row) {
+            versions.push(Version {
+                table_id: Some(id),
+                timestamp,
+                value: None,
+            });
+        }
+        versions
+    }
+
+    /// Returns up to `limit` versions of `row` from the latest with the timestamps.
+    /// Deleted versions are `None`.
+    pub fn history(&self, row: &Row, limit: usize) -> Vec<(u64, Option<Bytes>)> {
+        let mut versions = self.dump(row);
+        // Resolve versions with the same timestamp as `get_at` does:
+        // range tombstones win, then the memtable, and then newer tables.
+        versions.sort_by_key(|v| {
+            (
+                cmp::Reverse(v.timestamp),
+                v.value.is_some(),
+                v.table_id.is_some(),
+                cmp::Reverse(v.table_id),
+            )
+        });
+        versions.dedup_by_key(|v| v.timestamp);
+        versions
+            .into_iter()
+            .take(limit)
+            .map(|v| match v.value {
+                Some(Value::Val(v1)) => (v.timestamp, Some(v1)),
+                _ => (v.timestamp, None),
+            })
+            .collect()
+    }
+
+    fn update_memtable_metrics(&self) {
+        self.metrics.memtable_bytes.set(self.volatile.size() as u64);
+        self.metrics
+            .memtable_entries
+            .set(self.volatile.len() as u64);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeMap;
+
     #[test]
     pub fn flush() {
         let mut store = Store::new(2048);
@@ -103,4 +374,517 @@ mod tests {
         assert_eq!(5, k.timestamp());
         assert_eq!(v, Bytes::from("v35"));
     }
+
+    #[test]
+    pub fn delete_range() {
+        let mut store = Store::new(2048);
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+
+        store.put(row("a"), 1, Bytes::from("a1"));
+        store.put(row("b"), 1, Bytes::from("b1"));
+        store.put(row("c"), 1, Bytes::from("c1"));
+        store.flush(true);
+        store.put(row("b"), 3, Bytes::from("b3"));
+        store.delete_range(row("a"), row("c"), 2);
+
+        assert!(store.get_latest(&row("a")).is_none());
+        assert_eq!(store.get_latest(&row("b")).unwrap().1, Bytes::from("b3"));
+        assert_eq!(store.get_latest(&row("c")).unwrap().1, Bytes::from("c1"));
+
+        // tombstones are carried over by flush
+        store.flush(true);
+        assert!(store.get_latest(&row("a")).is_none());
+        assert_eq!(store.get_latest(&row("b")).unwrap().1, Bytes::from("b3"));
+        assert_eq!(store.get_latest(&row("c")).unwrap().1, Bytes::from("c1"));
+
+        // and by compaction
+        store.compact();
+        assert_eq!(1, store.persistent.num_tables());
+        assert_eq!(Some(2), store.persistent.get_latest_tombstone(&row("a")));
+        assert!(store.get_latest(&row("a")).is_none());
+        assert_eq!(store.get_latest(&row("b")).unwrap().1, Bytes::from("b3"));
+        assert_eq!(store.get_latest(&row("c")).unwrap().1, Bytes::from("c1"));
+
+        // so that they still shadow older versions written afterwards
+        store.put(row("a"), 1, Bytes::from("a1"));
+        store.delete(row("c"), 5);
+        store.compact();
+        store.put(row("c"), 4, Bytes::from("c4"));
+        assert!(store.get_latest(&row("a")).is_none());
+        assert!(store.get_latest(&row("c")).is_none());
+    }
+
+    #[test]
+    pub fn auto_flush() {
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+        let mut store = Store::new(16)
+            .with_memtable_size(Some(16))
+            .with_compaction_trigger(None);
+
+        store.put(row("a"), 1, Bytes::from("a1"));
+        assert_eq!(0, store.tables().len());
+        store.delete(row("b"), 2);
+        assert_eq!(0, store.tables().len());
+        store.put(row("c"), 3, Bytes::from("c3"));
+        assert_eq!(1, store.tables().len());
+        assert!(store.volatile.is_empty());
+
+        for t in 4..20 {
+            store.put(row("d"), t, Bytes::from("d"));
+        }
+        assert!(store.tables().len() > 1);
+        assert_eq!(0, store.metrics.compactions.get());
+        assert_eq!(Bytes::from("a1"), store.get_latest(&row("a")).unwrap().1);
+        assert!(store.get_latest(&row("b")).is_none());
+
+        store.compact();
+        assert_eq!(1, store.tables().len());
+
+        // range tombstones count towards the size as well
+        store.flush(true);
+        let tables = store.tables().len();
+        store.delete_range(row("e"), row("f"), 30);
+        assert_eq!(10, store.metrics.memtable_bytes.get());
+        store.delete_range(row("f"), row("g"), 31);
+        assert_eq!(tables + 1, store.tables().len());
+        assert!(store.volatile.is_empty());
+    }
+
+    #[test]
+    pub fn gc_horizon() {
+        let mut store = Store::new(2048);
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+        store.put(row("a"), 1, Bytes::from("a1"));
+        store.put(row("a"), 3, Bytes::from("a3"));
+        store.put(row("a"), 7, Bytes::from("a7"));
+        store.put(row("b"), 2, Bytes::from("b2"));
+        store.delete(row("b"), 4);
+        store.put(row("c"), 1, Bytes::from("c1"));
+        store.delete_range(row("c"), row("d"), 2);
+        store.put(row("c"), 6, Bytes::from("c6"));
+        store.put(row("d"), 1, Bytes::from("d1"));
+        store.delete_range(row("d"), row("e"), 6);
+        store.flush(true);
+
+        let reads = |store: &Store| {
+            let mut reads = vec![];
+            for r in &["a", "b", "c", "d"] {
+                for t in 5..10 {
+                    reads.push(store.get_at(&row(r), t));
+                }
+            }
+            reads
+        };
+        let expected = reads(&store);
+        store.persistent.compact(&store.options, Some(5));
+        assert_eq!(expected, reads(&store));
+
+        let timestamps = |r| {
+            store
+                .dump(&row(r))
+                .into_iter()
+                .map(|v| (v.timestamp, v.value.is_some()))
+                .collect::<Vec<_>>()
+        };
+        // only the latest version at or before the horizon is kept
+        assert_eq!(vec![(7, true), (3, true)], timestamps("a"));
+        // which is dropped if deleted
+        assert!(timestamps("b").is_empty());
+        assert_eq!(vec![(6, true)], timestamps("c"));
+        // tombstones after the horizon are kept
+        assert_eq!(vec![(1, true), (6, false)], timestamps("d"));
+    }
+
+    #[test]
+    pub fn dump() {
+        let mut store = Store::new(2048);
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+
+        store.put(row("a"), 1, Bytes::from("a1"));
+        store.flush(true);
+        store.delete_range(row("a"), row("b"), 2);
+        store.put(row("a"), 3, Bytes::from("a3"));
+        store.delete(row("a"), 4);
+
+        let versions = store
+            .dump(&row("a"))
+            .into_iter()
+            .map(|v| (v.table_id, v.timestamp, v.value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (None, 4, Some(Value::Del)),
+                (None, 3, Some(Value::Val(Bytes::from("a3")))),
+                (None, 2, None),
+                (Some(1), 1, Some(Value::Val(Bytes::from("a1")))),
+            ],
+            versions
+        );
+    }
+
+    #[test]
+    pub fn scan_prefix() {
+        let mut store = Store::new(2048).with_options(TableOptions {
+            filter: sstable::FilterKind::BlockedBloom,
+            prefix_extractor: Some(sstable::PrefixExtractor::Fixed(2)),
+            ..Default::default()
+        });
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+        store.put(row("a/1"), 1, Bytes::from("a1"));
+        store.put(row("a/2"), 2, Bytes::from("a2"));
+        store.put(row("b/1"), 3, Bytes::from("b1"));
+        store.flush(true);
+        store.put(row("a/2"), 4, Bytes::from("a2'"));
+        store.put(row("a/3"), 5, Bytes::from("a3"));
+        store.delete(row("a/1"), 6);
+        store.put(row("c/1"), 7, Bytes::from("c1"));
+        store.flush(true);
+        store.put(row("a/4"), 8, Bytes::from("a4"));
+        store.delete_range(row("a/3"), row("a/4"), 9);
+
+        let scan = |prefix: &[u8]| {
+            store
+                .scan(prefix, &[], 0)
+                .into_iter()
+                .map(|(k, v)| (k.row().clone(), k.timestamp(), v))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![
+                (row("a/2"), 4, Bytes::from("a2'")),
+                (row("a/4"), 8, Bytes::from("a4")),
+            ],
+            scan(b"a/")
+        );
+        assert_eq!(vec![(row("b/1"), 3, Bytes::from("b1"))], scan(b"b"));
+
+        // the second table has no rows with "b/"
+        let negatives = store.metrics().prefix_filter_negatives.get();
+        assert_eq!(vec![(row("b/1"), 3, Bytes::from("b1"))], scan(b"b/1"));
+        assert_eq!(negatives + 1, store.metrics().prefix_filter_negatives.get());
+        // neither of the tables have rows with "d/"
+        assert!(scan(b"d/").is_empty());
+        assert_eq!(negatives + 3, store.metrics().prefix_filter_negatives.get());
+
+        // pages skip the deleted rows
+        let rows = |start: &[u8], limit| {
+            store
+                .scan(b"", start, limit)
+                .into_iter()
+                .map(|(k, _)| k.row().clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![row("a/2"), row("a/4")], rows(b"", 2));
+        assert_eq!(vec![row("b/1"), row("c/1")], rows(b"a/4\0", 2));
+        assert!(rows(b"c/1\0", 2).is_empty());
+        assert_eq!(
+            vec![row("a/4")],
+            store
+                .scan(b"a/", b"a/3", 0)
+                .into_iter()
+                .map(|(k, _)| k.row().clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    pub fn skip_last_level_filters() {
+        let mut store = Store::new(2048).with_options(TableOptions {
+            filter: sstable::FilterKind::BlockedBloom,
+            skip_last_level_filters: true,
+            ..Default::default()
+        });
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+        store.put(row("r1"), 1, Bytes::from("v1"));
+        store.flush(true);
+        store.put(row("r2"), 2, Bytes::from("v2"));
+        store.flush(true);
+
+        let metrics = store.metrics();
+        assert_eq!(None, store.get_latest(&row("r3")));
+        assert_eq!(2, metrics.filter_negatives.get());
+
+        store.compact();
+        assert_eq!(1, store.tables().len());
+        assert_eq!(
+            Some(Bytes::from("v1")),
+            store.get_latest(&row("r1")).map(|(_, v)| v)
+        );
+        // the compacted table has no filter to reject the row
+        assert_eq!(None, store.get_latest(&row("r3")));
+        assert_eq!(2, metrics.filter_negatives.get());
+        assert_eq!(1, metrics.filter_false_positives.get());
+    }
+
+    #[test]
+    pub fn ingest() {
+        let mut store = Store::new(2048);
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+        store.put(row("a"), 5, Bytes::from("a5"));
+
+        let mut builder = sstable::SSTableBuilder::with_capacity(3, 0);
+        for &(r, timestamp) in &[("a", 7), ("a", 3), ("b", 1)] {
+            let value = Value::Val(Bytes::from(format!("{}{}", r, timestamp)));
+            builder.add(Key::new(row(r), timestamp), value).unwrap();
+        }
+        let id = store.ingest(builder.finish());
+
+        assert_eq!(
+            vec![id],
+            store.tables().iter().map(|t| t.id).collect::<Vec<_>>()
+        );
+        let get_at = |r, timestamp| {
+            store
+                .get_at(&row(r), timestamp)
+                .map(|(k, v)| (k.timestamp(), v))
+        };
+        assert_eq!(Some((7, Bytes::from("a7"))), get_at("a", 10));
+        assert_eq!(Some((5, Bytes::from("a5"))), get_at("a", 6));
+        assert_eq!(Some((3, Bytes::from("a3"))), get_at("a", 4));
+        assert_eq!(Some((1, Bytes::from("b1"))), get_at("b", 10));
+
+        store.flush(true);
+        assert_eq!(2, store.tables().len());
+        assert_eq!(Bytes::from("a7"), store.get_latest(&row("a")).unwrap().1);
+
+        // the newest table wins on the same key, also after compaction
+        for value in &["x", "y", "z"] {
+            let mut builder = sstable::SSTableBuilder::with_capacity(1, 0);
+            builder
+                .add(Key::new(row("a"), 7), Value::Val(Bytes::from(*value)))
+                .unwrap();
+            store.ingest(builder.finish());
+        }
+        let scan = |store: &Store| store.scan(b"", &[], 0).remove(0).1;
+        assert_eq!(Bytes::from("z"), store.get_latest(&row("a")).unwrap().1);
+        assert_eq!(Bytes::from("z"), scan(&store));
+        assert_eq!(Some(Bytes::from("z")), store.history(&row("a"), 1)[0].1);
+        store.compact();
+        assert_eq!(Bytes::from("z"), store.get_latest(&row("a")).unwrap().1);
+        assert_eq!(Bytes::from("z"), scan(&store));
+    }
+
+    #[test]
+    pub fn get_at() {
+        let mut store = Store::new(2048);
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+        store.put(row("a"), 1, Bytes::from("a1"));
+        store.put(row("a"), 3, Bytes::from("a3"));
+        store.flush(true);
+        store.delete(row("a"), 5);
+        store.put(row("a"), 7, Bytes::from("a7"));
+        store.delete_range(row("a"), row("b"), 8);
+
+        let get_at = |t| store.get_at(&row("a"), t).map(|(k, v)| (k.timestamp(), v));
+        assert_eq!(None, get_at(0));
+        assert_eq!(Some((1, Bytes::from("a1"))), get_at(2));
+        assert_eq!(Some((3, Bytes::from("a3"))), get_at(4));
+        assert_eq!(None, get_at(5));
+        assert_eq!(Some((7, Bytes::from("a7"))), get_at(7));
+        assert_eq!(None, get_at(8));
+        assert_eq!(None, store.get_latest(&row("a")));
+
+        assert_eq!(
+            vec![
+                (8, None),
+                (7, Some(Bytes::from("a7"))),
+                (5, None),
+                (3, Some(Bytes::from("a3"))),
+                (1, Some(Bytes::from("a1"))),
+            ],
+            store.history(&row("a"), 10)
+        );
+        assert_eq!(2, store.history(&row("a"), 2).len());
+        assert!(store.history(&row("b"), 10).is_empty());
+
+        // the filter is right about a row with only newer versions in the table
+        let metrics = store.metrics();
+        let positives = metrics.filter_true_positives.get();
+        assert_eq!(None, store.get_at(&row("a"), 0));
+        assert_eq!(positives + 1, metrics.filter_true_positives.get());
+        assert_eq!(0, metrics.filter_false_positives.get());
+
+        // compaction keeps all the versions
+        let history = store.history(&row("a"), 10);
+        store.compact();
+        assert_eq!(1, store.tables().len());
+        assert_eq!(history, store.history(&row("a"), 10));
+        assert_eq!(
+            Some(Bytes::from("a1")),
+            store.get_at(&row("a"), 2).map(|(_, v)| v)
+        );
+    }
+
+    #[test]
+    pub fn compaction_on_one_replica() {
+        let row = |r: &'static str| Row::new(Bytes::from(r));
+        let write = |store: &mut Store, t: u64, v: &str| {
+            store.put(row("a"), t, Bytes::from(format!("{}{}", v, t)));
+            store.delete(row("b"), t + 1);
+            store.delete_range(row("c"), row("d"), t + 2);
+            store.put(row("c"), t, Bytes::from(format!("{}{}", v, t)));
+        };
+        let mut replicas = [Store::new(16), Store::new(16)];
+        for (i, store) in replicas.iter_mut().enumerate() {
+            write(store, 10, "x");
+            store.flush(true);
+            write(store, 15, "x");
+            store.flush(true);
+            write(store, 20, "x");
+            // only the first replica is compacted by the admin service
+            if i == 0 {
+                store.compact();
+                assert_eq!(1, store.tables().len());
+            }
+            // versions older than the compacted ones
+            write(store, 5, "y");
+            // and the same keys as the compacted and the unflushed ones
+            write(store, 10, "y");
+            write(store, 20, "y");
+        }
+        assert_eq!(
+            Some(Bytes::from("y10")),
+            replicas[0].get_at(&row("a"), 10).map(|(_, v)| v)
+        );
+        assert_eq!(
+            Some(Bytes::from("y20")),
+            replicas[0].get_latest(&row("a")).map(|(_, v)| v)
+        );
+
+        let reads = |store: &Store| {
+            let mut reads = vec![];
+            for r in &["a", "b", "c"] {
+                for t in 0..30 {
+                    reads.push(store.get_at(&row(r), t));
+                }
+                reads.push(store.get_latest(&row(r)));
+            }
+            (reads, store.scan(b"", &[], 0), store.history(&row("a"), 10))
+        };
+        assert_eq!(reads(&replicas[1]), reads(&replicas[0]));
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Put(u8, u64),
+        Delete(u8, u64),
+        DeleteRange(u8, u8, u64),
+        Flush,
+        Compact,
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            8 => (0..8u8, 1..32u64).prop_map(|(r, t)| Op::Put(r, t)),
+            3 => (0..8u8, 1..32u64).prop_map(|(r, t)| Op::Delete(r, t)),
+            1 => (0..8u8, 1..4u8, 1..32u64).prop_map(|(r, l, t)| Op::DeleteRange(r, r + l, t)),
+            2 => Just(Op::Flush),
+            1 => Just(Op::Compact),
+        ]
+    }
+
+    /// Reference model of the rows written to `Store`, which flush and compaction never change
+    #[derive(Default)]
+    struct Model {
+        versions: BTreeMap<(u8, u64), Option<Bytes>>,
+        tombstones: Vec<(u8, u8, u64)>,
+    }
+
+    impl Model {
+        fn get_latest(&self, r: u8) -> Option<(u64, Bytes)> {
+            self.get_at(r, u64::max_value())
+        }
+
+        fn get_at(&self, r: u8, timestamp: u64) -> Option<(u64, Bytes)> {
+            let deleted_at = self
+                .tombstones
+                .iter()
+                .filter(|&&(start, end, t)| start <= r && r < end && t <= timestamp)
+                .map(|&(_, _, t)| t)
+                .max();
+            self.versions
+                .range((r, 0)..=(r, timestamp))
+                .next_back()
+                .and_then(|(&(_, t), v)| match v {
+                    Some(v) if deleted_at.map_or(true, |d| t > d) => Some((t, v.clone())),
+                    _ => None,
+                })
+        }
+    }
+
+    fn row(r: u8) -> Row {
+        Row::new(Bytes::from(format!("r{:02}", r)))
+    }
+
+    fn filter_kind() -> impl Strategy<Value = sstable::FilterKind> {
+        use sstable::FilterKind::*;
+        prop_oneof![
+            Just(Bloom),
+            Just(BlockedBloom),
+            Just(Cuckoo),
+            Just(Xor),
+            Just(None)
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn model(kind in filter_kind(), ops in proptest::collection::vec(op(), 1..64)) {
+            let mut store = Store::new(16).with_options(TableOptions {
+                filter: kind,
+                prefix_extractor: Some(sstable::PrefixExtractor::Fixed(2)),
+                ..Default::default()
+            });
+            let mut model = Model::default();
+            // keys may be written more than once, and then the last write wins
+            for (i, op) in ops.into_iter().enumerate() {
+                match op {
+                    Op::Put(r, t) => {
+                        let v = Bytes::from(format!("v{}", i));
+                        store.put(row(r), t, v.clone());
+                        model.versions.insert((r, t), Some(v));
+                    }
+                    Op::Delete(r, t) => {
+                        store.delete(row(r), t);
+                        model.versions.insert((r, t), None);
+                    }
+                    Op::DeleteRange(start, end, t) => {
+                        store.delete_range(row(start), row(end), t);
+                        model.tombstones.push((start, end, t));
+                    }
+                    Op::Flush => store.flush(true),
+                    Op::Compact => store.compact(),
+                }
+                for r in 0..12 {
+                    let actual = store.get_latest(&row(r)).map(|(k, v)| (k.timestamp(), v));
+                    prop_assert_eq!(model.get_latest(r), actual, "row={}", r);
+                    for t in (0..12).map(|t| t * 3) {
+                        let actual = store.get_at(&row(r), t).map(|(k, v)| (k.timestamp(), v));
+                        prop_assert_eq!(model.get_at(r, t), actual, "row={}, timestamp={}", r, t);
+                    }
+                }
+                for &(prefix, start, limit) in &[
+                    ("", "", 0),
+                    ("r0", "", 0),
+                    ("r1", "", 0),
+                    ("r05", "", 0),
+                    ("", "r04", 3),
+                    ("r0", "r06\0", 2),
+                ] {
+                    let expected = (0..12)
+                        .filter(|&r| row(r).get().starts_with(prefix.as_bytes()))
+                        .filter(|&r| row(r).get().as_ref() >= start.as_bytes())
+                        .filter_map(|r| model.get_latest(r).map(|(t, v)| (row(r), t, v)))
+                        .take(if limit == 0 { usize::max_value() } else { limit })
+                        .collect::<Vec<_>>();
+                    let actual = store
+                        .scan(prefix.as_bytes(), start.as_bytes(), limit)
+                        .into_iter()
+                        .map(|(k, v)| (k.row().clone(), k.timestamp(), v))
+                        .collect::<Vec<_>>();
+                    prop_assert_eq!(expected, actual, "prefix={:?}, start={:?}", prefix, start);
+                }
+            }
+        }
+    }
 }
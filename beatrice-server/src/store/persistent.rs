@@ -1,68 +1,290 @@
-use super::sstable::{Data, Filter, Index, SSTable};
+use super::{
+    metrics::StoreMetrics,
+    sstable::{Data, Filter, FilterKind, Index, SSTable, SSTableBuilder, TableOptions},
+    stat::Stat,
+    tombstone::RangeTombstones,
+};
 use crate::model::{Key, Row, Value};
-use std::collections::HashMap;
+use bytes::Bytes;
+use std::{cmp, collections::BTreeMap, sync::Arc};
 
-type TableId = u64;
+pub type TableId = u64;
+
+pub struct TableInfo {
+    pub id: TableId,
+    /// Number of entries in the table
+    pub len: usize,
+    /// Size of the data in bytes
+    pub size: usize,
+    pub tombstones: usize,
+    pub first_row: Option<Row>,
+    pub last_row: Option<Row>,
+}
 
 #[derive(Default)]
 pub struct PersistentStore {
     last_table_id: TableId,
 
     // TODO: implement better buffer pool
-    data_pool: HashMap<TableId, Data>,
-    index_pool: HashMap<TableId, Index>,
-    filter_pool: HashMap<TableId, Filter>,
+    // Ordered by id, so that newer tables win over older ones on the same key.
+    data_pool: BTreeMap<TableId, Data>,
+    index_pool: BTreeMap<TableId, Index>,
+    filter_pool: BTreeMap<TableId, Filter>,
+    tombstone_pool: BTreeMap<TableId, RangeTombstones>,
+
+    metrics: Arc<StoreMetrics>,
 }
 
 impl PersistentStore {
-    pub fn add(&mut self, sstable: SSTable, cache: bool) {
+    pub fn new(metrics: Arc<StoreMetrics>) -> Self {
+        Self {
+            metrics,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, sstable: SSTable, cache: bool) -> TableId {
         let table_id = self.last_table_id + 1;
+        self.last_table_id = table_id;
         let SSTable {
             data,
             index,
             filter,
+            tombstones,
+            ..
         } = sstable;
         if cache {
             self.data_pool.insert(table_id, data);
             self.index_pool.insert(table_id, index);
             self.filter_pool.insert(table_id, filter);
+            self.tombstone_pool.insert(table_id, tombstones);
         }
+        self.update_metrics();
+        table_id
     }
 
-    pub fn get_latest(&self, row: &Row) -> Option<(Key, Value)> {
-        // (TableId, (Key, Offset))
-        let mut res = None;
+    pub fn num_tables(&self) -> usize {
+        self.filter_pool.len()
+    }
+
+    /// Returns the latest version of `row` written at or before `timestamp`.
+    /// The newest table wins if several tables have the version.
+    pub fn get_at(&self, row: &Row, timestamp: u64) -> Option<(Key, Value)> {
+        let mut res: Option<(Key, Value)> = None;
         // Assuming that filters of all sstables are loaded
-        for (id, filter) in self.filter_pool.iter() {
+        for (id, filter) in self.filter_pool.iter().rev() {
             if filter.contains(row) {
-                // TODO: Handle cases when index is dropped from the buffer pool
+                // TODO: Handle cases when index or data is dropped from the buffer pool
                 let index = self
-                    .index_pool
-                    .get(id)
+                    .lookup(&self.index_pool, id)
                     .expect("assuming that indices of all sstables are loaded");
-                match (res, index.get_latest(row)) {
-                    (None, Some(p)) => {
-                        res = Some((id, p));
+                let data = self
+                    .lookup(&self.data_pool, id)
+                    .expect("assuming that data of all sstables are loaded");
+                let offset = index.seek(row);
+                let latest = data.get_at(row, timestamp, offset);
+                // the row may be in the table only with versions after `timestamp`
+                if latest.is_some() || data.get_versions(row, offset).next().is_some() {
+                    self.metrics.filter_true_positives.inc();
+                } else {
+                    self.metrics.filter_false_positives.inc();
+                }
+                match (&res, latest) {
+                    (None, Some(kv)) => {
+                        res = Some(kv);
                     }
-                    (Some((_, (key, _))), Some((k, o))) => {
-                        if key.timestamp() <= k.timestamp() {
-                            res = Some((id, (k, o)));
+                    (Some((key, _)), Some((k, v))) => {
+                        if key.timestamp() < k.timestamp() {
+                            res = Some((k, v));
                         }
                     }
                     _ => {}
                 }
+            } else {
+                self.metrics.filter_negatives.inc();
             }
         }
+        res
+    }
 
-        if let Some((id, (_, offset))) = res {
-            // TODO: Handle cases when data is dropped from the buffer pool
+    /// Returns the latest version of each row starting with `prefix` from `start`
+    /// in the tables whose filters may contain the prefix.
+    pub fn scan_prefix(&self, prefix: &[u8], start: &[u8]) -> BTreeMap<Row, (Key, Value)> {
+        let from = cmp::max(prefix, start);
+        let mut res = BTreeMap::<Row, (Key, Value)>::new();
+        for (id, filter) in self.filter_pool.iter().rev() {
+            if !filter.may_contain_prefix(prefix) {
+                self.metrics.prefix_filter_negatives.inc();
+                continue;
+            }
+            self.metrics.prefix_filter_positives.inc();
+            let index = self
+                .lookup(&self.index_pool, id)
+                .expect("assuming that indices of all sstables are loaded");
             let data = self
-                .data_pool
-                .get(id)
+                .lookup(&self.data_pool, id)
                 .expect("assuming that data of all sstables are loaded");
-            Some(data.get(offset))
+            let offset = index.seek(&Row::new(Bytes::copy_from_slice(from)));
+            let records = data.scan_prefix(prefix, offset);
+            for (k, v) in records.skip_while(|(k, _)| k.row().get().as_ref() < from) {
+                match res.get(k.row()) {
+                    Some((key, _)) if key.timestamp() >= k.timestamp() => {}
+                    _ => {
+                        res.insert(k.row().clone(), (k, v));
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    pub fn tables(&self) -> Vec<TableInfo> {
+        self.index_pool
+            .iter()
+            .map(|(&id, index)| {
+                let data = self.data_pool.get(&id);
+                TableInfo {
+                    id,
+                    len: data.map_or(0, |data| data.len()),
+                    size: data.map_or(0, |data| data.size()),
+                    tombstones: self.tombstone_pool.get(&id).map_or(0, |t| t.len()),
+                    first_row: index.iter().next().map(|(k, _)| k.row().clone()),
+                    last_row: data
+                        .and_then(|data| data.iter().last())
+                        .map(|(_, k, _)| k.row().clone()),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the versions of `row` in each table with the table id.
+    pub fn get_versions(&self, row: &Row) -> Vec<(TableId, Key, Value)> {
+        self.index_pool
+            .iter()
+            .filter_map(|(&id, index)| Some((id, index, self.data_pool.get(&id)?)))
+            .flat_map(|(id, index, data)| {
+                data.get_versions(row, index.seek(row))
+                    .map(move |(k, v)| (id, k, v))
+            })
+            .collect()
+    }
+
+    /// Returns the timestamps of the range tombstones covering `row` with the table ids.
+    pub fn get_tombstones(&self, row: &Row) -> Vec<(TableId, u64)> {
+        let mut tombstones = self
+            .tombstone_pool
+            .iter()
+            .flat_map(|(&id, tombstones)| {
+                tombstones
+                    .iter()
+                    .filter(move |t| t.contains(row))
+                    .map(move |t| (id, t.timestamp()))
+            })
+            .collect::<Vec<_>>();
+        tombstones.sort();
+        tombstones
+    }
+
+    /// Returns the largest timestamp of the range tombstones covering `row`.
+    pub fn get_latest_tombstone(&self, row: &Row) -> Option<u64> {
+        self.get_latest_tombstone_at(row, u64::max_value())
+    }
+
+    pub fn get_latest_tombstone_at(&self, row: &Row, timestamp: u64) -> Option<u64> {
+        self.tombstone_pool
+            .values()
+            .filter_map(|tombstones| tombstones.get_latest_at(row, timestamp))
+            .max()
+    }
+
+    /// Merges all the tables into a single table.
+    ///
+    /// The merged table holds all the versions and range tombstones, since reads at past
+    /// timestamps may see any version and tombstones shadow versions written later with
+    /// older timestamps. A key written to several tables is kept once from the newest table,
+    /// which is also the one reads return, so compaction never changes what reads return.
+    ///
+    /// If `horizon` is given, only reads at or after it are preserved instead.
+    /// Of the versions at or before the horizon, only the latest of each row is kept unless
+    /// it is deleted, and the range tombstones at or before the horizon are dropped.
+    /// Versions written at or before the horizon afterwards may thus be visible
+    /// even if they are older than a dropped deletion.
+    pub fn compact(&mut self, options: &TableOptions, horizon: Option<u64>) {
+        let mut tombstones = RangeTombstones::default();
+        for t in self.tombstone_pool.values() {
+            tombstones.extend(t.iter().cloned());
+        }
+
+        let mut entries = Vec::new();
+        for (id, data) in self.data_pool.iter() {
+            entries.extend(data.iter().map(|(_, k, v)| (*id, (k, v))));
+        }
+        // Newer tables take precedence over older ones on the same key.
+        entries.sort_by(|(id1, (k1, _)), (id2, (k2, _))| k1.cmp(k2).then(id2.cmp(id1)));
+        entries.dedup_by(|(_, (k1, _)), (_, (k2, _))| k1 == k2);
+
+        if let Some(horizon) = horizon {
+            let mut last_row: Option<Row> = None;
+            entries.retain(|(_, (k, v))| {
+                if k.timestamp() > horizon {
+                    return true;
+                }
+                // versions come from the latest, and the older ones are never read
+                if last_row.as_ref() == Some(k.row()) {
+                    return false;
+                }
+                last_row = Some(k.row().clone());
+                let deleted_at = tombstones.get_latest_at(k.row(), horizon);
+                matches!(v, Value::Val(_)) && deleted_at.map_or(true, |t| k.timestamp() > t)
+            });
+            let mut live = RangeTombstones::default();
+            live.extend(
+                tombstones
+                    .iter()
+                    .filter(|t| t.timestamp() > horizon)
+                    .cloned(),
+            );
+            tombstones = live;
+        }
+
+        self.data_pool.clear();
+        self.index_pool.clear();
+        self.filter_pool.clear();
+        self.tombstone_pool.clear();
+        if entries.is_empty() && tombstones.is_empty() {
+            self.update_metrics();
+            return;
+        }
+
+        let mut stat = Stat::default();
+        for (_, (k, v)) in entries.iter() {
+            stat.insert(k, v, None);
+        }
+        let mut builder = SSTableBuilder::new(&stat).options(options);
+        if options.skip_last_level_filters {
+            builder = builder.filter_kind(FilterKind::None);
+        }
+        let sstable = builder.load(entries.into_iter().map(|(_, kv)| kv), tombstones);
+        self.add(sstable, true);
+    }
+
+    fn lookup<'a, T>(&self, pool: &'a BTreeMap<TableId, T>, id: &TableId) -> Option<&'a T> {
+        let v = pool.get(id);
+        if v.is_some() {
+            self.metrics.cache_hits.inc();
         } else {
-            None
+            self.metrics.cache_misses.inc();
         }
+        v
+    }
+
+    fn update_metrics(&self) {
+        self.metrics.sstables.set(self.filter_pool.len() as u64);
+        let bytes = self
+            .data_pool
+            .values()
+            .map(|data| data.size())
+            .sum::<usize>();
+        self.metrics.sstable_bytes.set(bytes as u64);
     }
 }
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+/// Codec to compress data blocks of an SSTable with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd,
+    Snappy,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::None
+    }
+}
+
+impl Compression {
+    const NONE: u8 = 0;
+    const LZ4: u8 = 1;
+    const ZSTD: u8 = 2;
+    const SNAPPY: u8 = 3;
+
+    const ZSTD_LEVEL: i32 = 3;
+
+    /// Identifier of the codec recorded in block headers
+    pub fn id(self) -> u8 {
+        match self {
+            Compression::None => Self::NONE,
+            Compression::Lz4 => Self::LZ4,
+            Compression::Zstd => Self::ZSTD,
+            Compression::Snappy => Self::SNAPPY,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            Self::NONE => Some(Compression::None),
+            Self::LZ4 => Some(Compression::Lz4),
+            Self::ZSTD => Some(Compression::Zstd),
+            Self::SNAPPY => Some(Compression::Snappy),
+            _ => None,
+        }
+    }
+
+    pub fn compress(self, block: &[u8]) -> Vec<u8> {
+        match self {
+            Compression::None => block.to_vec(),
+            Compression::Lz4 => lz4_flex::compress(block),
+            Compression::Zstd => zstd::block::compress(block, Self::ZSTD_LEVEL)
+                .expect("compressing into a vector never fails"),
+            Compression::Snappy => snap::raw::Encoder::new()
+                .compress_vec(block)
+                .expect("block is smaller than the maximum size of snappy"),
+        }
+    }
+
+    /// Decompresses a block whose uncompressed size is `len`.
+    /// Returns `None` if the block is corrupted.
+    pub fn decompress(self, block: &[u8], len: usize) -> Option<Vec<u8>> {
+        let decompressed = match self {
+            Compression::None => block.to_vec(),
+            Compression::Lz4 => lz4_flex::decompress(block, len).ok()?,
+            Compression::Zstd => zstd::block::decompress(block, len).ok()?,
+            // the header tells the size to allocate, which may be corrupted
+            Compression::Snappy if snap::raw::decompress_len(block).ok()? != len => return None,
+            Compression::Snappy => snap::raw::Decoder::new().decompress_vec(block).ok()?,
+        };
+        if decompressed.len() == len {
+            Some(decompressed)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_decompress() {
+        let block = b"beatrice beatrice beatrice beatrice".repeat(16);
+        for &c in &[
+            Compression::None,
+            Compression::Lz4,
+            Compression::Zstd,
+            Compression::Snappy,
+        ] {
+            assert_eq!(Some(c), Compression::from_id(c.id()));
+            let compressed = c.compress(&block);
+            assert_eq!(
+                Some(&block),
+                c.decompress(&compressed, block.len()).as_ref(),
+                "compression={:?}",
+                c
+            );
+            assert_eq!(None, c.decompress(&compressed, block.len() + 1));
+        }
+
+        // a snappy header claiming 1 GiB
+        let block = [0x80, 0x80, 0x80, 0x80, 0x04, 0x00];
+        assert_eq!(Some(1 << 30), snap::raw::decompress_len(&block).ok());
+        assert_eq!(None, Compression::Snappy.decompress(&block, 16));
+    }
+}
@@ -1,40 +1,264 @@
-use crate::model::{Key, Value};
-use bytes::{Bytes, BytesMut};
+//! Records of an SSTable with prefix-compressed keys.
+//!
+//! Each record is encoded as
+//!
+//! ```text
+//! shared: varint | unshared: varint | row suffix | timestamp | value
+//! ```
+//!
+//! where `shared` is the length of the prefix shared with the row of the previous record.
+//! Every [`RESTART_INTERVAL`] records, a restart point stores the whole row (`shared` = 0)
+//! so that lookups can start decoding from there.
+//!
+//! The encoding of the timestamp and the value depends on the [`RecordFormat`].
+//! Tables written before prefix compression store whole keys instead ([`RecordFormat::V0`]).
+
+use crate::{
+    model::{Key, Row, Value},
+    varint,
+};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+/// Number of records between restart points
+pub const RESTART_INTERVAL: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Whole rows with `u64` timestamps and lengths, where every record is a restart point
+    V0,
+    /// Varint timestamps and values with `u64` length prefixes
+    V1,
+    /// Zigzag varint deltas from the timestamp of the previous record (or 0 at restart points)
+    /// and values with varint length prefixes
+    V2,
+}
+
+impl RecordFormat {
+    pub const LATEST: RecordFormat = RecordFormat::V2;
+}
+
+impl Default for RecordFormat {
+    fn default() -> Self {
+        Self::LATEST
+    }
+}
 
 #[derive(Clone, Default)]
 pub struct Data {
     data: Bytes,
+    len: usize,
+    format: RecordFormat,
 }
 
 impl Data {
-    pub fn get(&self, offset: usize) -> (Key, Value) {
-        let mut buf = self.data.slice(offset..);
-        let key = Key::read_from(&mut buf);
-        let value = Value::read_from(&mut buf);
-        (key, value)
+    /// Creates data from `len` records encoded in `format`.
+    pub fn new(data: Bytes, len: usize, format: RecordFormat) -> Self {
+        Self { data, len, format }
+    }
+
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Number of records
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the latest version of `row`, scanning from the restart point at `offset`.
+    pub fn get_latest(&self, row: &Row, offset: usize) -> Option<(Key, Value)> {
+        self.get_at(row, u64::max_value(), offset)
+    }
+
+    /// Returns the latest version of `row` written at or before `timestamp`,
+    /// scanning from the restart point at `offset`.
+    pub fn get_at(&self, row: &Row, timestamp: u64, offset: usize) -> Option<(Key, Value)> {
+        let target = Key::new(row.clone(), timestamp);
+        let (key, value) = self
+            .iter_from(offset)
+            .map(|(_, k, v)| (k, v))
+            .find(|(k, _)| k >= &target)?;
+        if key.row() == row {
+            Some((key, value))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the versions of `row` from the latest,
+    /// scanning from the restart point at `offset`.
+    pub fn get_versions<'a>(
+        &self,
+        row: &'a Row,
+        offset: usize,
+    ) -> impl Iterator<Item = (Key, Value)> + 'a {
+        self.iter_from(offset)
+            .map(|(_, k, v)| (k, v))
+            .skip_while(move |(k, _)| k.row() < row)
+            .take_while(move |(k, _)| k.row() == row)
+    }
+
+    /// Returns the records whose rows start with `prefix`,
+    /// scanning from the restart point at `offset`.
+    pub fn scan_prefix<'a>(
+        &self,
+        prefix: &'a [u8],
+        offset: usize,
+    ) -> impl Iterator<Item = (Key, Value)> + 'a {
+        self.iter_from(offset)
+            .map(|(_, k, v)| (k, v))
+            .skip_while(move |(k, _)| k.row().get().as_ref() < prefix)
+            .take_while(move |(k, _)| k.row().get().starts_with(prefix))
+    }
+
+    /// Returns an iterator over the records with their offsets.
+    pub fn iter(&self) -> Iter {
+        self.iter_from(0)
+    }
+
+    /// Returns an iterator over the records from the restart point at `offset`.
+    fn iter_from(&self, offset: usize) -> Iter {
+        Iter {
+            buf: self.data.slice(offset..),
+            format: self.format,
+            offset,
+            n: 0,
+            row: BytesMut::new(),
+            timestamp: 0,
+        }
+    }
+}
+
+pub struct Iter {
+    buf: Bytes,
+    format: RecordFormat,
+    offset: usize,
+    /// Number of records read so far
+    n: usize,
+    row: BytesMut,
+    timestamp: u64,
+}
+
+impl Iterator for Iter {
+    type Item = (usize, Key, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.buf.has_remaining() {
+            return None;
+        }
+        let offset = self.offset;
+        let len = self.buf.len();
+        if self.format == RecordFormat::V0 {
+            let key = Key::read_from_v0(&mut self.buf)?;
+            let value = Value::read_from_v1(&mut self.buf)?;
+            self.n += 1;
+            self.offset += len - self.buf.len();
+            return Some((offset, key, value));
+        }
+
+        let shared = varint::get(&mut self.buf)? as usize;
+        let unshared = varint::get(&mut self.buf)? as usize;
+        if shared > self.row.len() || unshared > self.buf.remaining() {
+            return None;
+        }
+        self.row.truncate(shared);
+        self.row.extend_from_slice(&self.buf[..unshared]);
+        self.buf.advance(unshared);
+        let (timestamp, value) = match self.format {
+            RecordFormat::V0 => unreachable!(),
+            RecordFormat::V1 => {
+                let timestamp = varint::get(&mut self.buf)?;
+                (timestamp, Value::read_from_v1(&mut self.buf)?)
+            }
+            RecordFormat::V2 => {
+                let base = if self.n % RESTART_INTERVAL == 0 {
+                    0
+                } else {
+                    self.timestamp
+                };
+                let delta = varint::unzigzag(varint::get(&mut self.buf)?);
+                let timestamp = base.wrapping_add(delta as u64);
+                (timestamp, Value::read_from(&mut self.buf)?)
+            }
+        };
+
+        self.n += 1;
+        self.timestamp = timestamp;
+        self.offset += len - self.buf.len();
+        let row = Row::new(Bytes::copy_from_slice(&self.row));
+        Some((offset, Key::new(row, timestamp), value))
     }
 }
 
+/// A builder of data in the latest record format.
 pub struct DataBuilder {
     data: BytesMut,
+    len: usize,
+    last_row: Bytes,
+    last_timestamp: u64,
 }
 
 impl DataBuilder {
     pub fn new(size: usize) -> Self {
         Self {
             data: BytesMut::with_capacity(size),
+            len: 0,
+            last_row: Bytes::new(),
+            last_timestamp: 0,
         }
     }
 
-    pub fn append(&mut self, key: Key, value: Value) -> usize {
-        let key_size = key.write_to(&mut self.data);
-        let value_size = value.write_to(&mut self.data);
-        key_size + value_size
+    /// Appends a record.
+    /// Returns the offset of the record if it is a restart point.
+    pub fn append(&mut self, key: Key, value: Value) -> Option<usize> {
+        let offset = self.data.len();
+        let restart = self.len % RESTART_INTERVAL == 0;
+        let row = key.row().get();
+        let (shared, base) = if restart {
+            (0, 0)
+        } else {
+            let shared = row
+                .iter()
+                .zip(self.last_row.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            (shared, self.last_timestamp)
+        };
+        let delta = key.timestamp().wrapping_sub(base) as i64;
+
+        varint::put(&mut self.data, shared as u64);
+        varint::put(&mut self.data, (row.len() - shared) as u64);
+        self.data.put_slice(&row[shared..]);
+        varint::put(&mut self.data, varint::zigzag(delta));
+        value.write_to(&mut self.data);
+
+        self.last_row = row.clone();
+        self.last_timestamp = key.timestamp();
+        self.len += 1;
+        if restart {
+            Some(offset)
+        } else {
+            None
+        }
     }
 
     pub fn build(self) -> Data {
         Data {
             data: self.data.freeze(),
+            len: self.len,
+            format: RecordFormat::LATEST,
         }
     }
 }
@@ -42,39 +266,175 @@ impl DataBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::Row;
+
+    fn kvs() -> Vec<(Key, Value)> {
+        (0..40u64)
+            .map(|i| {
+                let key = Key::new(Row::new(Bytes::from(format!("row{:03}", i / 2))), 100 - i);
+                let value = if i % 5 == 0 {
+                    Value::Del
+                } else {
+                    Value::Val(Bytes::from(format!("v{}", i)))
+                };
+                (key, value)
+            })
+            .collect()
+    }
+
+    fn build(kvs: &[(Key, Value)]) -> (Data, Vec<(Key, usize)>) {
+        let mut builder = DataBuilder::new(0);
+        let mut restarts = Vec::new();
+        for (key, value) in kvs.iter().cloned() {
+            if let Some(offset) = builder.append(key.clone(), value) {
+                restarts.push((key, offset));
+            }
+        }
+        (builder.build(), restarts)
+    }
 
     #[test]
     fn test() {
+        let kvs = kvs();
+        let (data, restarts) = build(&kvs);
+
+        assert_eq!(kvs.len(), data.len());
+        assert_eq!(
+            (kvs.len() + RESTART_INTERVAL - 1) / RESTART_INTERVAL,
+            restarts.len()
+        );
+        let records = data.iter().map(|(_, k, v)| (k, v)).collect::<Vec<_>>();
+        assert_eq!(kvs, records);
+        for (key, offset) in restarts {
+            let (_, k, _) = data.iter_from(offset).next().unwrap();
+            assert_eq!(key, k);
+        }
+    }
+
+    #[test]
+    fn test_get_latest() {
+        let kvs = kvs();
+        let (data, restarts) = build(&kvs);
+
+        // row005 consists of records #10 and #11 in the first restart interval
+        let row = Row::new(Bytes::from("row005"));
+        assert_eq!(Some(kvs[10].clone()), data.get_latest(&row, 0));
+        // row010 consists of records #20 and #21 in the second restart interval
+        let row = Row::new(Bytes::from("row010"));
+        assert_eq!(Some(kvs[20].clone()), data.get_latest(&row, 0));
+        assert_eq!(Some(kvs[20].clone()), data.get_latest(&row, restarts[1].1));
+
+        let row = Row::new(Bytes::from("row0055"));
+        assert_eq!(None, data.get_latest(&row, 0));
+        let row = Row::new(Bytes::from("row999"));
+        assert_eq!(None, data.get_latest(&row, restarts[2].1));
+    }
+
+    #[test]
+    fn test_scan_prefix() {
+        let kvs = kvs();
+        let (data, restarts) = build(&kvs);
+
+        // row010 to row019 are records #20 to #39 from the middle of the second restart interval
+        let offset = restarts[1].1;
+        let records = data.scan_prefix(b"row01", offset).collect::<Vec<_>>();
+        assert_eq!(kvs[20..].to_vec(), records);
+        let records = data.scan_prefix(b"row005", 0).collect::<Vec<_>>();
+        assert_eq!(kvs[10..12].to_vec(), records);
+        assert_eq!(0, data.scan_prefix(b"row1", 0).count());
+    }
+
+    #[test]
+    fn test_prefix_compression() {
+        let kvs = kvs();
+        let (data, _) = build(&kvs);
+
+        // size of the records with whole rows and timestamps
+        let raw_size = kvs.iter().map(|(k, v)| k.size() + v.size()).sum::<usize>();
+        assert!(data.size() < raw_size);
+    }
+
+    #[test]
+    fn test_timestamp_delta() {
         let kvs = vec![
             (
-                Key::new(Row::new(Bytes::from("k1")), 1),
-                Value::Val(Bytes::from("v1")),
-            ),
-            (
-                Key::new(Row::new(Bytes::from("k10")), 10),
-                Value::Val(Bytes::from("v10")),
+                Key::new(Row::new(Bytes::from("a")), u64::max_value()),
+                Value::Del,
             ),
-            (Key::new(Row::new(Bytes::from("k100")), 100), Value::Del),
+            (Key::new(Row::new(Bytes::from("a")), 0), Value::Del),
+            (Key::new(Row::new(Bytes::from("b")), 1 << 40), Value::Del),
             (
-                Key::new(Row::new(Bytes::from("k100")), 100),
-                Value::Val(Bytes::from("v100")),
+                Key::new(Row::new(Bytes::from("b")), (1 << 40) - 1),
+                Value::Del,
             ),
         ];
-        let mut builder = DataBuilder::new(kvs.len());
-        let mut offsets = Vec::with_capacity(kvs.len());
-        let mut offset = 0;
-        for (key, value) in kvs.iter().cloned() {
-            let len = builder.append(key, value);
-            offsets.push(offset);
-            offset += len;
-        }
-        let data = builder.build();
+        let (data, _) = build(&kvs);
 
-        for ((key, value), offset) in kvs.into_iter().zip(offsets.into_iter()) {
-            let (k, v) = data.get(offset);
-            assert_eq!(key, k);
-            assert_eq!(value, v);
-        }
+        let records = data.iter().map(|(_, k, v)| (k, v)).collect::<Vec<_>>();
+        assert_eq!(kvs, records);
+    }
+
+    #[test]
+    fn test_read_v0() {
+        // "row1"@10 => "v" and "row2"@9 => deleted
+        let mut buf = BytesMut::new();
+        buf.put_u64_le(10);
+        buf.put_u64_le(4);
+        buf.put_slice(b"row1");
+        buf.put_u8(0);
+        buf.put_u64_le(1);
+        buf.put_slice(b"v");
+        let offset = buf.len();
+        buf.put_u64_le(9);
+        buf.put_u64_le(4);
+        buf.put_slice(b"row2");
+        buf.put_u8(1);
+        let data = Data::new(buf.freeze(), 2, RecordFormat::V0);
+
+        let records = data.iter().collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    0,
+                    Key::new(Row::new(Bytes::from("row1")), 10),
+                    Value::Val(Bytes::from("v"))
+                ),
+                (
+                    offset,
+                    Key::new(Row::new(Bytes::from("row2")), 9),
+                    Value::Del
+                ),
+            ],
+            records
+        );
+        let row = Row::new(Bytes::from("row2"));
+        assert_eq!(
+            Some((Key::new(row.clone(), 9), Value::Del)),
+            data.get_latest(&row, offset)
+        );
+    }
+
+    #[test]
+    fn test_read_v1() {
+        // "row1"@10 => "v" and "row2"@9 => deleted, the second sharing "row"
+        let mut buf = BytesMut::new();
+        buf.put_slice(&[0, 4]);
+        buf.put_slice(b"row1");
+        buf.put_slice(&[10, 0]);
+        buf.put_u64_le(1);
+        buf.put_slice(b"v");
+        buf.put_slice(&[3, 1, b'2', 9, 1]);
+        let data = Data::new(buf.freeze(), 2, RecordFormat::V1);
+
+        let records = data.iter().map(|(_, k, v)| (k, v)).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    Key::new(Row::new(Bytes::from("row1")), 10),
+                    Value::Val(Bytes::from("v"))
+                ),
+                (Key::new(Row::new(Bytes::from("row2")), 9), Value::Del),
+            ],
+            records
+        );
     }
 }
@@ -0,0 +1,960 @@
+//! File format of an SSTable.
+//!
+//! ```text
+//! +------+-------+--------+------------+------+--------+
+//! | data | index | filter | tombstones | meta | footer |
+//! +------+-------+--------+------------+------+--------+
+//! ```
+//!
+//! The footer has a fixed size and locates the other blocks with their checksums.
+//! The data block is split into chunks of about [`CHUNK_SIZE`] bytes at record boundaries,
+//! each of which is compressed independently and prefixed by a [`ChunkHeader`]
+//! (from version 2, before which the data block is stored as it is).
+//! All integers are encoded in little-endian.
+
+use super::{Compression, Data, Filter, FilterKind, Index, PrefixExtractor, RecordFormat, SSTable};
+use crate::{
+    model::{Key, RangeTombstone, Row},
+    store::tombstone::RangeTombstones,
+    varint,
+};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::{error, fmt, iter};
+
+/// "beatrice" in little-endian
+pub const MAGIC: u64 = 0x6563_6972_7461_6562;
+/// Version of the format written by [`encode`]
+///
+/// - 1: uncompressed data block of whole keys ([`RecordFormat::V0`]) and an index with `u64` integers
+/// - 2: data block split into compressed chunks
+/// - 3: rows prefix-compressed with restart points and fixed-size value lengths ([`RecordFormat::V1`])
+/// - 4: records with varint value lengths and timestamp deltas ([`RecordFormat::V2`])
+/// - 5: the filter block begins with the [`FilterKind`]
+/// - 6: the filter block records the [`PrefixExtractor`]
+///
+/// Tables in older record formats are written in the last version of their format as they are.
+pub const FORMAT_VERSION: u8 = 6;
+/// Size of uncompressed data chunks
+pub const CHUNK_SIZE: usize = 4096;
+/// Largest ratio of the uncompressed to the compressed size of a chunk.
+/// Chunks which would compress better are written uncompressed,
+/// so that decoding never allocates much more than the size of the file.
+pub const MAX_COMPRESSION_RATIO: u64 = 255;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockHandle {
+    pub offset: u64,
+    pub len: u64,
+    /// CRC32 of the block
+    pub checksum: u32,
+}
+
+impl BlockHandle {
+    const SIZE: usize = 8 + 8 + 4;
+
+    fn write_to<T: BufMut>(&self, buf: &mut T) {
+        buf.put_u64_le(self.offset);
+        buf.put_u64_le(self.len);
+        buf.put_u32_le(self.checksum);
+    }
+
+    fn read_from(buf: &mut Bytes) -> Self {
+        let offset = buf.get_u64_le();
+        let len = buf.get_u64_le();
+        let checksum = buf.get_u32_le();
+        Self {
+            offset,
+            len,
+            checksum,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Footer {
+    pub data: BlockHandle,
+    pub index: BlockHandle,
+    pub filter: BlockHandle,
+    pub tombstones: BlockHandle,
+    pub meta: BlockHandle,
+    pub version: u8,
+}
+
+impl Footer {
+    pub const SIZE: usize = 5 * BlockHandle::SIZE + 1 + 8;
+}
+
+/// Header of a compressed chunk of the data block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkHeader {
+    pub compression: Compression,
+    pub uncompressed_len: u32,
+    pub compressed_len: u32,
+}
+
+/// Properties of a table stored in the meta block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableMeta {
+    pub entries: u64,
+    pub range_tombstones: u64,
+    pub min_timestamp: u64,
+    pub max_timestamp: u64,
+}
+
+impl TableMeta {
+    fn of(sstable: &SSTable) -> Self {
+        let timestamps = || sstable.data.iter().map(|(_, k, _)| k.timestamp());
+        Self {
+            entries: sstable.data.len() as u64,
+            range_tombstones: sstable.tombstones.len() as u64,
+            min_timestamp: timestamps().min().unwrap_or(0),
+            max_timestamp: timestamps().max().unwrap_or(0),
+        }
+    }
+}
+
+/// An SSTable decoded from a file.
+pub struct TableFile {
+    pub footer: Footer,
+    pub chunks: Vec<ChunkHeader>,
+    pub meta: TableMeta,
+    pub sstable: SSTable,
+}
+
+pub fn encode(sstable: &SSTable) -> Bytes {
+    let version = format_version(sstable.data.format());
+    let blocks = [
+        encode_data(sstable),
+        encode_index(&sstable.index, version),
+        encode_filter(&sstable.filter, version),
+        encode_tombstones(&sstable.tombstones),
+        encode_meta(&TableMeta::of(sstable)),
+    ];
+    write_blocks(&blocks, version)
+}
+
+/// Writes `blocks` followed by the footer locating them.
+fn write_blocks(blocks: &[Bytes], version: u8) -> Bytes {
+    let mut buf = BytesMut::new();
+    let mut handles = Vec::with_capacity(blocks.len());
+    for block in blocks.iter() {
+        handles.push(BlockHandle {
+            offset: buf.len() as u64,
+            len: block.len() as u64,
+            checksum: crc32fast::hash(block),
+        });
+        buf.put_slice(block);
+    }
+    for handle in handles.iter() {
+        handle.write_to(&mut buf);
+    }
+    buf.put_u8(version);
+    buf.put_u64_le(MAGIC);
+    buf.freeze()
+}
+
+/// Decodes an SSTable file.
+/// Checksums of the blocks are verified only if `verify_checksums` is set.
+pub fn decode(buf: Bytes, verify_checksums: bool) -> Result<TableFile, FormatError> {
+    let footer = decode_footer(&buf)?;
+    let body_len = (buf.len() - Footer::SIZE) as u64;
+    let block = |name: &'static str, handle: &BlockHandle| {
+        let end = handle
+            .offset
+            .checked_add(handle.len)
+            .filter(|&end| end <= body_len)
+            .ok_or(FormatError::Corrupted(name))?;
+        let block = buf.slice(handle.offset as usize..end as usize);
+        if verify_checksums {
+            let actual = crc32fast::hash(&block);
+            if actual != handle.checksum {
+                return Err(FormatError::ChecksumMismatch {
+                    block: name,
+                    expected: handle.checksum,
+                    actual,
+                });
+            }
+        }
+        Ok(Reader { buf: block, name })
+    };
+
+    let (data, chunks) = match footer.version {
+        1 => (block("data", &footer.data)?.buf, Vec::new()),
+        _ => decode_data(block("data", &footer.data)?)?,
+    };
+    let index = decode_index(block("index", &footer.index)?, data.len(), footer.version)?;
+    let filter = decode_filter(block("filter", &footer.filter)?, footer.version)?;
+    let tombstones = decode_tombstones(block("tombstones", &footer.tombstones)?)?;
+    let meta = decode_meta(block("meta", &footer.meta)?)?;
+    let format = record_format(footer.version).expect("version is checked in the footer");
+    let data = Data::new(data, meta.entries as usize, format);
+
+    let compression = chunks
+        .iter()
+        .map(|c| c.compression)
+        .find(|&c| c != Compression::None)
+        .unwrap_or(Compression::None);
+    Ok(TableFile {
+        footer,
+        chunks,
+        meta,
+        sstable: SSTable {
+            data,
+            index,
+            filter,
+            tombstones,
+            compression,
+        },
+    })
+}
+
+/// Verifies that the records in `file` are sorted without duplicates and consistent
+/// with the other blocks, which checksums alone cannot ensure for externally built tables.
+pub fn verify(file: &TableFile) -> Result<(), FormatError> {
+    let TableFile { meta, sstable, .. } = file;
+    let mut index = sstable.index.iter().peekable();
+    let mut last: Option<Key> = None;
+    let mut n = 0;
+    let (mut min_timestamp, mut max_timestamp) = (u64::max_value(), 0);
+    for (offset, key, _) in sstable.data.iter() {
+        if last.as_ref().map_or(false, |last| last >= &key) {
+            return Err(FormatError::Unsorted);
+        }
+        if let Some((k, _)) = index.next_if(|(_, o)| *o == offset) {
+            if k != &key {
+                return Err(FormatError::Corrupted("index"));
+            }
+        }
+        if !sstable.filter.contains(key.row()) {
+            return Err(FormatError::Corrupted("filter"));
+        }
+        min_timestamp = min_timestamp.min(key.timestamp());
+        max_timestamp = max_timestamp.max(key.timestamp());
+        last = Some(key);
+        n += 1;
+    }
+    if n != sstable.data.len() {
+        return Err(FormatError::Corrupted("data"));
+    }
+    if index.next().is_some() {
+        return Err(FormatError::Corrupted("index"));
+    }
+    if n > 0 && (meta.min_timestamp, meta.max_timestamp) != (min_timestamp, max_timestamp) {
+        return Err(FormatError::Corrupted("meta"));
+    }
+    Ok(())
+}
+
+fn decode_footer(buf: &Bytes) -> Result<Footer, FormatError> {
+    if buf.len() < Footer::SIZE {
+        return Err(FormatError::TooShort(buf.len()));
+    }
+    let mut footer = buf.slice(buf.len() - Footer::SIZE..);
+    let data = BlockHandle::read_from(&mut footer);
+    let index = BlockHandle::read_from(&mut footer);
+    let filter = BlockHandle::read_from(&mut footer);
+    let tombstones = BlockHandle::read_from(&mut footer);
+    let meta = BlockHandle::read_from(&mut footer);
+    let version = footer.get_u8();
+    let magic = footer.get_u64_le();
+    if magic != MAGIC {
+        return Err(FormatError::BadMagic(magic));
+    }
+    if record_format(version).is_none() {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+    Ok(Footer {
+        data,
+        index,
+        filter,
+        tombstones,
+        meta,
+        version,
+    })
+}
+
+fn format_version(format: RecordFormat) -> u8 {
+    match format {
+        RecordFormat::V0 => 2,
+        RecordFormat::V1 => 3,
+        RecordFormat::V2 => FORMAT_VERSION,
+    }
+}
+
+fn record_format(version: u8) -> Option<RecordFormat> {
+    match version {
+        1 | 2 => Some(RecordFormat::V0),
+        3 => Some(RecordFormat::V1),
+        4..=FORMAT_VERSION => Some(RecordFormat::V2),
+        _ => None,
+    }
+}
+
+fn encode_data(sstable: &SSTable) -> Bytes {
+    let data = sstable.data.as_bytes();
+    let offsets = sstable.index.iter().map(|(_, offset)| *offset);
+
+    let mut buf = BytesMut::new();
+    let mut start = 0;
+    for end in offsets.chain(iter::once(data.len())) {
+        if end - start >= CHUNK_SIZE || (end == data.len() && end > start) {
+            let mut compression = sstable.compression;
+            let mut chunk = compression.compress(&data[start..end]);
+            if chunk.len() as u64 * MAX_COMPRESSION_RATIO < (end - start) as u64 {
+                compression = Compression::None;
+                chunk = data[start..end].to_vec();
+            }
+            buf.put_u8(compression.id());
+            buf.put_u32_le((end - start) as u32);
+            buf.put_u32_le(chunk.len() as u32);
+            buf.put_slice(&chunk);
+            start = end;
+        }
+    }
+    buf.freeze()
+}
+
+fn decode_data(mut r: Reader) -> Result<(Bytes, Vec<ChunkHeader>), FormatError> {
+    let mut data = BytesMut::new();
+    let mut chunks = Vec::new();
+    while r.buf.has_remaining() {
+        let id = r.get_u8()?;
+        let compression = Compression::from_id(id).ok_or(FormatError::UnknownCodec(id))?;
+        let uncompressed_len = r.get_u32()?;
+        let compressed_len = r.get_u32()?;
+        // reject the lengths before allocating a buffer for them
+        if uncompressed_len as u64 > compressed_len as u64 * MAX_COMPRESSION_RATIO {
+            return Err(FormatError::Corrupted(r.name));
+        }
+        let chunk = r.get_bytes(compressed_len as u64)?;
+        let chunk = compression
+            .decompress(&chunk, uncompressed_len as usize)
+            .ok_or(FormatError::Corrupted(r.name))?;
+        data.put_slice(&chunk);
+        chunks.push(ChunkHeader {
+            compression,
+            uncompressed_len,
+            compressed_len,
+        });
+    }
+    Ok((data.freeze(), chunks))
+}
+
+/// `n | (row, timestamp, offset) * n` in varints,
+/// or in `u64` with the timestamp first up to version 2.
+fn encode_index(index: &Index, version: u8) -> Bytes {
+    let mut buf = BytesMut::new();
+    if version <= 2 {
+        buf.put_u64_le(index.len() as u64);
+        for (key, offset) in index.iter() {
+            buf.put_u64_le(key.timestamp());
+            buf.put_u64_le(key.row().get().len() as u64);
+            buf.put_slice(key.row().get());
+            buf.put_u64_le(*offset as u64);
+        }
+        return buf.freeze();
+    }
+    varint::put(&mut buf, index.len() as u64);
+    for (key, offset) in index.iter() {
+        varint::put(&mut buf, key.row().get().len() as u64);
+        buf.put_slice(key.row().get());
+        varint::put(&mut buf, key.timestamp());
+        varint::put(&mut buf, *offset as u64);
+    }
+    buf.freeze()
+}
+
+fn decode_index(mut r: Reader, data_len: usize, version: u8) -> Result<Index, FormatError> {
+    let fixed = version <= 2;
+    let n = if fixed { r.get_u64()? } else { r.get_varint()? };
+    let mut index = Vec::new();
+    for _ in 0..n {
+        let (row, timestamp, offset) = if fixed {
+            let timestamp = r.get_u64()?;
+            let len = r.get_u64()?;
+            let row = Row::new(r.get_bytes(len)?);
+            (row, timestamp, r.get_u64()?)
+        } else {
+            let len = r.get_varint()?;
+            let row = Row::new(r.get_bytes(len)?);
+            (row, r.get_varint()?, r.get_varint()?)
+        };
+        let offset = offset as usize;
+        if offset >= data_len {
+            return Err(FormatError::Corrupted(r.name));
+        }
+        index.push((Key::new(row, timestamp), offset));
+    }
+    Ok(Index::new(index))
+}
+
+/// `kind: u8 | prefix extractor | k: u64 | m: u64 | filter`, where the filter occupies
+/// the rest of the block and `k` is the number of bits of a fingerprint for cuckoo and xor filters.
+///
+/// The prefix extractor is an id (0 for none) followed by its parameter in a varint.
+fn encode_filter(filter: &Filter, version: u8) -> Bytes {
+    let mut buf = BytesMut::new();
+    if version >= 5 {
+        buf.put_u8(filter.kind().id());
+    } else {
+        debug_assert_eq!(FilterKind::Bloom, filter.kind());
+    }
+    if version >= 6 {
+        match filter.prefix_extractor() {
+            Some(extractor) => {
+                let (id, param) = extractor.to_id();
+                buf.put_u8(id);
+                varint::put(&mut buf, param);
+            }
+            None => buf.put_u8(0),
+        }
+    } else {
+        debug_assert_eq!(None, filter.prefix_extractor());
+    }
+    buf.put_u64_le(filter.k() as u64);
+    buf.put_u64_le(filter.m() as u64);
+    buf.put_slice(&filter.to_bytes());
+    buf.freeze()
+}
+
+fn decode_filter(mut r: Reader, version: u8) -> Result<Filter, FormatError> {
+    let kind = if version >= 5 {
+        let id = r.get_u8()?;
+        FilterKind::from_id(id).ok_or(FormatError::UnknownFilter(id))?
+    } else {
+        FilterKind::Bloom
+    };
+    let prefix_extractor = match version {
+        6..=FORMAT_VERSION => match r.get_u8()? {
+            0 => None,
+            id => {
+                let param = r.get_varint()?;
+                Some(PrefixExtractor::from_id(id, param).ok_or(FormatError::Corrupted(r.name))?)
+            }
+        },
+        _ => None,
+    };
+    let k = r.get_u64()? as usize;
+    let m = r.get_u64()?;
+    Filter::from_bytes(kind, k, m, &r.buf, prefix_extractor).ok_or(FormatError::Corrupted(r.name))
+}
+
+fn encode_tombstones(tombstones: &RangeTombstones) -> Bytes {
+    let mut buf = BytesMut::new();
+    buf.put_u64_le(tombstones.len() as u64);
+    for t in tombstones.iter() {
+        buf.put_u64_le(t.start().get().len() as u64);
+        buf.put_slice(t.start().get());
+        buf.put_u64_le(t.end().get().len() as u64);
+        buf.put_slice(t.end().get());
+        buf.put_u64_le(t.timestamp());
+    }
+    buf.freeze()
+}
+
+fn decode_tombstones(mut r: Reader) -> Result<RangeTombstones, FormatError> {
+    let n = r.get_u64()?;
+    let mut tombstones = RangeTombstones::default();
+    for _ in 0..n {
+        let len = r.get_u64()?;
+        let start = Row::new(r.get_bytes(len)?);
+        let len = r.get_u64()?;
+        let end = Row::new(r.get_bytes(len)?);
+        let timestamp = r.get_u64()?;
+        if start >= end {
+            return Err(FormatError::Corrupted(r.name));
+        }
+        tombstones.insert(RangeTombstone::new(start, end, timestamp));
+    }
+    Ok(tombstones)
+}
+
+fn encode_meta(meta: &TableMeta) -> Bytes {
+    let mut buf = BytesMut::new();
+    buf.put_u64_le(meta.entries);
+    buf.put_u64_le(meta.range_tombstones);
+    buf.put_u64_le(meta.min_timestamp);
+    buf.put_u64_le(meta.max_timestamp);
+    buf.freeze()
+}
+
+fn decode_meta(mut r: Reader) -> Result<TableMeta, FormatError> {
+    Ok(TableMeta {
+        entries: r.get_u64()?,
+        range_tombstones: r.get_u64()?,
+        min_timestamp: r.get_u64()?,
+        max_timestamp: r.get_u64()?,
+    })
+}
+
+/// A reader of a block that fails instead of panicking on truncated input.
+struct Reader {
+    buf: Bytes,
+    name: &'static str,
+}
+
+impl Reader {
+    fn get_u8(&mut self) -> Result<u8, FormatError> {
+        if self.buf.remaining() < 1 {
+            return Err(FormatError::Corrupted(self.name));
+        }
+        Ok(self.buf.get_u8())
+    }
+
+    fn get_u32(&mut self) -> Result<u32, FormatError> {
+        if self.buf.remaining() < 4 {
+            return Err(FormatError::Corrupted(self.name));
+        }
+        Ok(self.buf.get_u32_le())
+    }
+
+    fn get_u64(&mut self) -> Result<u64, FormatError> {
+        if self.buf.remaining() < 8 {
+            return Err(FormatError::Corrupted(self.name));
+        }
+        Ok(self.buf.get_u64_le())
+    }
+
+    fn get_varint(&mut self) -> Result<u64, FormatError> {
+        varint::get(&mut self.buf).ok_or(FormatError::Corrupted(self.name))
+    }
+
+    fn get_bytes(&mut self, len: u64) -> Result<Bytes, FormatError> {
+        if (self.buf.remaining() as u64) < len {
+            return Err(FormatError::Corrupted(self.name));
+        }
+        Ok(self.buf.split_to(len as usize))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FormatError {
+    TooShort(usize),
+    BadMagic(u64),
+    UnsupportedVersion(u8),
+    UnknownCodec(u8),
+    UnknownFilter(u8),
+    ChecksumMismatch {
+        block: &'static str,
+        expected: u32,
+        actual: u32,
+    },
+    Corrupted(&'static str),
+    /// Keys in the data block are not in order or duplicated
+    Unsorted,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::TooShort(len) => write!(f, "file is too short: {} bytes", len),
+            FormatError::BadMagic(magic) => write!(f, "bad magic number: {:#x}", magic),
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version: {}", version)
+            }
+            FormatError::UnknownCodec(id) => write!(f, "unknown compression codec: {}", id),
+            FormatError::UnknownFilter(id) => write!(f, "unknown filter kind: {}", id),
+            FormatError::ChecksumMismatch {
+                block,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch in {} block: expected={:#010x}, actual={:#010x}",
+                block, expected, actual
+            ),
+            FormatError::Corrupted(block) => write!(f, "{} block is corrupted", block),
+            FormatError::Unsorted => write!(f, "keys in data block are not sorted"),
+        }
+    }
+}
+
+impl error::Error for FormatError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        model::Value,
+        store::{
+            sstable::{data::DataBuilder, FilterBuilder, FilterSize, SSTableBuilder},
+            stat::Stat,
+        },
+    };
+
+    fn build() -> SSTable {
+        let kvs = vec![
+            (
+                Key::new(Row::new(Bytes::from("a")), 10),
+                Value::Val(Bytes::from("a10")),
+            ),
+            (Key::new(Row::new(Bytes::from("b")), 30), Value::Del),
+            (
+                Key::new(Row::new(Bytes::from("c")), 20),
+                Value::Val(Bytes::from("c20")),
+            ),
+        ];
+        let mut stat = Stat::default();
+        for (k, v) in kvs.iter() {
+            stat.insert(k, v, None);
+        }
+        let mut tombstones = RangeTombstones::default();
+        tombstones.insert(RangeTombstone::new(
+            Row::new(Bytes::from("x")),
+            Row::new(Bytes::from("z")),
+            5,
+        ));
+        SSTableBuilder::new(&stat).load(kvs, tombstones)
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let sstable = build();
+        let file = decode(encode(&sstable), true).unwrap();
+
+        assert_eq!(FORMAT_VERSION, file.footer.version);
+        assert_eq!(
+            TableMeta {
+                entries: 3,
+                range_tombstones: 1,
+                min_timestamp: 10,
+                max_timestamp: 30,
+            },
+            file.meta
+        );
+        let expected = sstable.index.iter().cloned().collect::<Vec<_>>();
+        let actual = file.sstable.index.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(expected, actual);
+        let expected = sstable.data.iter().collect::<Vec<_>>();
+        let actual = file.sstable.data.iter().collect::<Vec<_>>();
+        assert_eq!(expected, actual);
+        assert_eq!(sstable.filter.to_bytes(), file.sstable.filter.to_bytes());
+        assert!(file.sstable.filter.contains(&Row::new(Bytes::from("c"))));
+        assert_eq!(
+            Some(5),
+            file.sstable
+                .tombstones
+                .get_latest(&Row::new(Bytes::from("y")))
+        );
+    }
+
+    #[test]
+    fn test_decode_corrupted() {
+        let buf = encode(&build());
+
+        // corrupt the first record right after the chunk header
+        let mut corrupted = BytesMut::from(&buf[..]);
+        corrupted[9] ^= 0xff;
+        let corrupted = corrupted.freeze();
+        assert!(matches!(
+            decode(corrupted.clone(), true),
+            Err(FormatError::ChecksumMismatch { block: "data", .. })
+        ));
+        assert!(decode(corrupted, false).is_ok());
+
+        let mut unknown_codec = BytesMut::from(&buf[..]);
+        unknown_codec[0] = 0xff;
+        assert_eq!(
+            Some(FormatError::UnknownCodec(0xff)),
+            decode(unknown_codec.freeze(), false).err()
+        );
+
+        let mut bad_magic = BytesMut::from(&buf[..]);
+        let n = bad_magic.len();
+        bad_magic[n - 1] ^= 0xff;
+        assert!(matches!(
+            decode(bad_magic.freeze(), true),
+            Err(FormatError::BadMagic(_))
+        ));
+
+        assert_eq!(
+            Some(FormatError::TooShort(3)),
+            decode(buf.slice(..3), true).err()
+        );
+
+        let mut unsupported = BytesMut::from(&buf[..]);
+        let n = unsupported.len();
+        unsupported[n - 9] = FORMAT_VERSION + 1;
+        assert_eq!(
+            Some(FormatError::UnsupportedVersion(FORMAT_VERSION + 1)),
+            decode(unsupported.freeze(), false).err()
+        );
+    }
+
+    #[test]
+    fn test_decode_torn() {
+        let buf = encode(&build());
+
+        // a write torn at any point is detected
+        for n in 0..buf.len() {
+            assert!(decode(buf.slice(..n), true).is_err(), "len={}", n);
+            let _ = decode(buf.slice(..n), false);
+        }
+
+        // so is a flipped bit anywhere in the file
+        for i in 0..buf.len() {
+            for b in 0..8 {
+                let mut flipped = BytesMut::from(&buf[..]);
+                flipped[i] ^= 1 << b;
+                let flipped = flipped.freeze();
+                assert!(decode(flipped.clone(), true).is_err(), "i={}, b={}", i, b);
+                let _ = decode(flipped, false);
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_kind() {
+        let mut stat = Stat::default();
+        let kvs = (0..100u64)
+            .map(|i| {
+                let kv = (
+                    Key::new(Row::new(Bytes::from(format!("row{:03}", i))), i),
+                    Value::Del,
+                );
+                stat.insert(&kv.0, &kv.1, None);
+                kv
+            })
+            .collect::<Vec<_>>();
+        for &kind in [
+            FilterKind::BlockedBloom,
+            FilterKind::Cuckoo,
+            FilterKind::Xor,
+        ]
+        .iter()
+        {
+            let sstable = SSTableBuilder::new(&stat)
+                .filter_kind(kind)
+                .load(kvs.clone(), RangeTombstones::default());
+
+            let file = decode(encode(&sstable), true).unwrap();
+            assert_eq!(kind, file.sstable.filter.kind());
+            assert_eq!(sstable.filter.to_bytes(), file.sstable.filter.to_bytes());
+            assert!(kvs
+                .iter()
+                .all(|(k, _)| file.sstable.filter.contains(k.row())));
+        }
+    }
+
+    #[test]
+    fn test_prefix_extractor() {
+        let mut stat = Stat::default();
+        let kvs = ["a/1", "a/2", "b/1"]
+            .iter()
+            .map(|row| {
+                let kv = (Key::new(Row::new(Bytes::from(*row)), 1), Value::Del);
+                stat.insert(&kv.0, &kv.1, None);
+                kv
+            })
+            .collect::<Vec<_>>();
+        let extractor = Some(PrefixExtractor::Delimiter('/'));
+        let sstable = SSTableBuilder::new(&stat)
+            .prefix_extractor(extractor)
+            .load(kvs, RangeTombstones::default());
+
+        let file = decode(encode(&sstable), true).unwrap();
+        let filter = &file.sstable.filter;
+        assert_eq!(extractor, filter.prefix_extractor());
+        assert!(filter.may_contain_prefix(b"a/"));
+        assert!(filter.may_contain_prefix(b"b/"));
+        assert!(!filter.may_contain_prefix(b"c/"));
+    }
+
+    #[test]
+    fn test_decode_v0() {
+        // "a"@10 => "a10" and "b"@30 => deleted encoded in the record format of versions 1 and 2
+        let mut data = BytesMut::new();
+        let a = Key::new(Row::new(Bytes::from("a")), 10);
+        let b = Key::new(Row::new(Bytes::from("b")), 30);
+        data.put_u64_le(10);
+        data.put_u64_le(1);
+        data.put_slice(b"a");
+        data.put_u8(0);
+        data.put_u64_le(3);
+        data.put_slice(b"a10");
+        let offset = data.len();
+        data.put_u64_le(30);
+        data.put_u64_le(1);
+        data.put_slice(b"b");
+        data.put_u8(1);
+        let mut filter = FilterBuilder::new(FilterKind::Bloom, FilterSize::default());
+        filter.insert(a.row().clone());
+        filter.insert(b.row().clone());
+        let sstable = SSTable {
+            data: Data::new(data.freeze(), 2, RecordFormat::V0),
+            index: Index::new(vec![(a.clone(), 0), (b.clone(), offset)]),
+            filter: filter.build(),
+            tombstones: RangeTombstones::default(),
+            compression: Compression::None,
+        };
+        let v2 = encode(&sstable);
+        // version 1 stores the data block without chunk headers
+        let v1 = write_blocks(
+            &[
+                sstable.data.as_bytes().clone(),
+                encode_index(&sstable.index, 1),
+                encode_filter(&sstable.filter, 1),
+                encode_tombstones(&sstable.tombstones),
+                encode_meta(&TableMeta::of(&sstable)),
+            ],
+            1,
+        );
+
+        for (version, buf) in &[(1, v1), (2, v2)] {
+            let file = decode(buf.clone(), true).unwrap();
+            assert_eq!(*version, file.footer.version);
+            assert_eq!(RecordFormat::V0, file.sstable.data.format());
+            assert_eq!(Ok(()), verify(&file));
+            assert_eq!(
+                Some((b.clone(), Value::Del)),
+                file.sstable.data.get_latest(b.row(), offset)
+            );
+            let records = file.sstable.data.iter().map(|(_, k, v)| (k, v));
+            assert_eq!(
+                vec![
+                    (a.clone(), Value::Val(Bytes::from("a10"))),
+                    (b.clone(), Value::Del)
+                ],
+                records.collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_v1() {
+        // "a"@10 => "a10" encoded in the record format of version 3
+        let mut data = BytesMut::new();
+        data.put_slice(&[0, 1, b'a', 10, 0]);
+        data.put_u64_le(3);
+        data.put_slice(b"a10");
+        let key = Key::new(Row::new(Bytes::from("a")), 10);
+        let sstable = SSTable {
+            data: Data::new(data.freeze(), 1, RecordFormat::V1),
+            index: Index::new(vec![(key.clone(), 0)]),
+            filter: FilterBuilder::new(FilterKind::Bloom, FilterSize::default()).build(),
+            tombstones: RangeTombstones::default(),
+            compression: Compression::None,
+        };
+
+        let file = decode(encode(&sstable), true).unwrap();
+        assert_eq!(3, file.footer.version);
+        assert_eq!(RecordFormat::V1, file.sstable.data.format());
+        assert_eq!(
+            Some((key, Value::Val(Bytes::from("a10")))),
+            file.sstable.data.get_latest(&Row::new(Bytes::from("a")), 0)
+        );
+    }
+
+    #[test]
+    fn test_compression() {
+        let kvs = (0..1000u64)
+            .map(|i| {
+                (
+                    Key::new(Row::new(Bytes::from(format!("row{:04}", i))), i),
+                    Value::Val(Bytes::from(format!("value{:04}", i))),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut stat = Stat::default();
+        for (k, v) in kvs.iter() {
+            stat.insert(k, v, None);
+        }
+
+        for &compression in &[
+            Compression::None,
+            Compression::Lz4,
+            Compression::Zstd,
+            Compression::Snappy,
+        ] {
+            let sstable = SSTableBuilder::new(&stat)
+                .compression(compression)
+                .load(kvs.clone(), RangeTombstones::default());
+            let buf = encode(&sstable);
+            let file = decode(buf.clone(), true).unwrap();
+
+            assert_eq!(compression, file.sstable.compression);
+            assert!(file.chunks.len() > 1);
+            assert!(file.chunks.iter().all(|c| c.compression == compression));
+            assert_eq!(sstable.data.as_bytes(), file.sstable.data.as_bytes());
+            if compression != Compression::None {
+                assert!(file.footer.data.len < sstable.data.size() as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        // a chunk of zeros compresses too well to be written compressed
+        let key = Key::new(Row::new(Bytes::from("a")), 1);
+        let value = Value::Val(Bytes::from(vec![0; 64 * 1024]));
+        let mut builder = SSTableBuilder::with_capacity(1, 0).compression(Compression::Zstd);
+        builder.add(key, value).unwrap();
+        let buf = encode(&builder.finish());
+        let file = decode(buf.clone(), true).unwrap();
+        assert_eq!(Compression::None, file.chunks[0].compression);
+        assert_eq!(Compression::None, file.sstable.compression);
+
+        // nor is such a chunk decoded
+        let mut forged = BytesMut::from(&buf[..]);
+        forged[0] = Compression::Lz4.id();
+        forged[1..5].copy_from_slice(&u32::max_value().to_le_bytes());
+        assert_eq!(
+            Some(FormatError::Corrupted("data")),
+            decode(forged.freeze(), false).err()
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let file = decode(encode(&build()), true).unwrap();
+        assert_eq!(Ok(()), verify(&file));
+
+        // builds a table without the checks of SSTableBuilder
+        let file = |keys: &[(&'static str, u64)], rows: &[&'static str]| {
+            let mut data = DataBuilder::new(0);
+            let mut index = vec![];
+            for &(row, timestamp) in keys {
+                let key = Key::new(Row::new(Bytes::from(row)), timestamp);
+                if let Some(offset) = data.append(key.clone(), Value::Del) {
+                    index.push((key, offset));
+                }
+            }
+            let mut filter = FilterBuilder::new(FilterKind::BlockedBloom, FilterSize::default());
+            for &row in rows {
+                filter.insert(Row::new(Bytes::from(row)));
+            }
+            let sstable = SSTable {
+                data: data.build(),
+                index: Index::new(index),
+                filter: filter.build(),
+                tombstones: RangeTombstones::default(),
+                compression: Compression::None,
+            };
+            decode(encode(&sstable), true).unwrap()
+        };
+
+        let sorted = file(&[("a", 2), ("a", 1), ("b", 1)], &["a", "b"]);
+        assert_eq!(Ok(()), verify(&sorted));
+        assert_eq!(Ok(()), verify(&file(&[], &[])));
+        assert_eq!(
+            Err(FormatError::Unsorted),
+            verify(&file(&[("b", 1), ("a", 1)], &["a", "b"]))
+        );
+        assert_eq!(
+            Err(FormatError::Unsorted),
+            verify(&file(&[("a", 1), ("a", 2)], &["a"]))
+        );
+        assert_eq!(
+            Err(FormatError::Unsorted),
+            verify(&file(&[("a", 1), ("a", 1)], &["a"]))
+        );
+        assert_eq!(
+            Err(FormatError::Corrupted("filter")),
+            verify(&file(&[("a", 1), ("b", 1)], &["a"]))
+        );
+
+        let mut meta = sorted;
+        meta.meta.max_timestamp = 3;
+        assert_eq!(Err(FormatError::Corrupted("meta")), verify(&meta));
+    }
+}
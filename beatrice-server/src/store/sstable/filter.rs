@@ -1,14 +1,446 @@
-use crate::collections::bloom_filter::BloomFilter;
+use super::{compute_filter_bits, PrefixExtractor};
+use crate::collections::{
+    bloom_filter::{self, BlockedBloomFilter, BloomFilter},
+    cuckoo_filter::{self, CuckooFilter},
+    membership_filter::MembershipFilter,
+    xor_filter::{self, XorFilter},
+};
 use crate::model::Row;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// Kind of the filter of an SSTable
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    Bloom,
+    /// A bloom-filter blocked by cache lines
+    BlockedBloom,
+    Cuckoo,
+    Xor,
+    /// No filter, with which every lookup reads the table
+    None,
+}
+
+impl Default for FilterKind {
+    fn default() -> Self {
+        FilterKind::Bloom
+    }
+}
+
+impl FilterKind {
+    const BLOOM: u8 = 0;
+    const BLOCKED_BLOOM: u8 = 1;
+    const CUCKOO: u8 = 2;
+    const XOR: u8 = 3;
+    const NONE: u8 = 4;
+
+    /// Identifier of the kind recorded in SSTable files
+    pub fn id(self) -> u8 {
+        match self {
+            FilterKind::Bloom => Self::BLOOM,
+            FilterKind::BlockedBloom => Self::BLOCKED_BLOOM,
+            FilterKind::Cuckoo => Self::CUCKOO,
+            FilterKind::Xor => Self::XOR,
+            FilterKind::None => Self::NONE,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            Self::BLOOM => Some(FilterKind::Bloom),
+            Self::BLOCKED_BLOOM => Some(FilterKind::BlockedBloom),
+            Self::CUCKOO => Some(FilterKind::Cuckoo),
+            Self::XOR => Some(FilterKind::Xor),
+            Self::NONE => Some(FilterKind::None),
+            _ => None,
+        }
+    }
+}
+
+/// Target size of a filter
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterSize {
+    FalsePositiveRate(f64),
+    /// Number of bits per row (and prefix)
+    BitsPerKey(f64),
+}
+
+impl Default for FilterSize {
+    fn default() -> Self {
+        FilterSize::FalsePositiveRate(0.001)
+    }
+}
+
+#[derive(Clone)]
+enum Membership {
+    Bloom(BloomFilter<Row>),
+    BlockedBloom(BlockedBloomFilter<Row>),
+    Cuckoo(CuckooFilter<Row>),
+    Xor(XorFilter<Row>),
+    None,
+}
+
+/// A filter of the rows of an SSTable, which also holds their prefixes
+/// if the table has a prefix extractor.
+#[derive(Clone)]
 pub struct Filter {
-    filter: BloomFilter<Row>,
+    membership: Membership,
+    prefix_extractor: Option<PrefixExtractor>,
 }
 
 impl Filter {
-    pub fn new(filter: BloomFilter<Row>) -> Self {
-        Self { filter }
+    pub fn kind(&self) -> FilterKind {
+        match self.membership {
+            Membership::Bloom(_) => FilterKind::Bloom,
+            Membership::BlockedBloom(_) => FilterKind::BlockedBloom,
+            Membership::Cuckoo(_) => FilterKind::Cuckoo,
+            Membership::Xor(_) => FilterKind::Xor,
+            Membership::None => FilterKind::None,
+        }
     }
+
+    /// Number of hash functions of bloom-filters, or number of bits of a fingerprint of the others
+    pub fn k(&self) -> usize {
+        match &self.membership {
+            Membership::Bloom(f) => f.k(),
+            Membership::BlockedBloom(f) => f.k(),
+            Membership::Cuckoo(f) => f.bits() as usize,
+            Membership::Xor(f) => f.bits() as usize,
+            Membership::None => 0,
+        }
+    }
+
+    pub fn prefix_extractor(&self) -> Option<PrefixExtractor> {
+        self.prefix_extractor
+    }
+
     pub fn contains(&self, row: &Row) -> bool {
-        self.filter.contains(row)
+        self.inner().map_or(true, |f| f.contains(row))
+    }
+
+    /// Returns `false` if no row of the table starts with `prefix`.
+    ///
+    /// Rows starting with `prefix` share its extracted prefix,
+    /// so the filter can answer only if `prefix` itself has one.
+    pub fn may_contain_prefix(&self, prefix: &[u8]) -> bool {
+        match self.prefix_extractor.and_then(|e| e.extract(prefix)) {
+            Some(p) => self.contains(&Row::new(Bytes::copy_from_slice(p))),
+            None => true,
+        }
+    }
+
+    pub fn m(&self) -> usize {
+        self.inner().map_or(0, |f| f.m())
+    }
+
+    pub fn false_positive_rate(&self, n: usize) -> f64 {
+        self.inner().map_or(1.0, |f| f.false_positive_rate(n))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.inner().map_or_else(Vec::new, |f| f.to_bytes())
+    }
+
+    /// Restore a filter from `k`, `m` and the bytes returned by [`Filter::to_bytes`].
+    /// Returns `None` if they are inconsistent.
+    pub fn from_bytes(
+        kind: FilterKind,
+        k: usize,
+        m: u64,
+        bytes: &[u8],
+        prefix_extractor: Option<PrefixExtractor>,
+    ) -> Option<Self> {
+        let bloom_size = (m + 7) / 8;
+        let bloom_valid = m > 0 && (1..=bloom_filter::MAX_K).contains(&k);
+        let membership = match kind {
+            FilterKind::Bloom if bloom_valid && bytes.len() as u64 == bloom_size => {
+                Membership::Bloom(BloomFilter::from_bytes(k, m, bytes))
+            }
+            FilterKind::BlockedBloom if bloom_valid && bytes.len() as u64 == bloom_size => {
+                Membership::BlockedBloom(BlockedBloomFilter::from_bytes(k, m, bytes))
+            }
+            FilterKind::Bloom | FilterKind::BlockedBloom => return None,
+            FilterKind::Cuckoo => Membership::Cuckoo(CuckooFilter::from_bytes(k as u32, m, bytes)?),
+            FilterKind::Xor => Membership::Xor(XorFilter::from_bytes(k as u32, m, bytes)?),
+            FilterKind::None if m == 0 && bytes.is_empty() => Membership::None,
+            FilterKind::None => return None,
+        };
+        Some(Self {
+            membership,
+            prefix_extractor,
+        })
+    }
+
+    fn inner(&self) -> Option<&dyn MembershipFilter<Row>> {
+        match &self.membership {
+            Membership::Bloom(f) => Some(f),
+            Membership::BlockedBloom(f) => Some(f),
+            Membership::Cuckoo(f) => Some(f),
+            Membership::Xor(f) => Some(f),
+            Membership::None => None,
+        }
+    }
+}
+
+/// A builder of a filter of a target size.
+///
+/// Rows are kept until [`FilterBuilder::build`] since some filters have to be built at once.
+pub struct FilterBuilder {
+    kind: FilterKind,
+    size: FilterSize,
+    prefix_extractor: Option<PrefixExtractor>,
+    rows: Vec<Row>,
+    prefixes: Vec<Row>,
+}
+
+impl FilterBuilder {
+    pub fn new(kind: FilterKind, size: FilterSize) -> Self {
+        Self {
+            kind,
+            size,
+            prefix_extractor: None,
+            rows: Vec::new(),
+            prefixes: Vec::new(),
+        }
+    }
+
+    pub fn with_kind(self, kind: FilterKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    pub fn with_size(self, size: FilterSize) -> Self {
+        Self { size, ..self }
+    }
+
+    pub fn with_prefix_extractor(self, prefix_extractor: Option<PrefixExtractor>) -> Self {
+        Self {
+            prefix_extractor,
+            ..self
+        }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    /// Inserts `row` and its prefix. Rows are expected to be inserted in order,
+    /// and a row equal to the last one is skipped.
+    pub fn insert(&mut self, row: Row) {
+        if self.kind == FilterKind::None || self.rows.last() == Some(&row) {
+            return;
+        }
+        let prefix = self.prefix_extractor.and_then(|e| e.extract(row.get()));
+        if let Some(prefix) = prefix {
+            if self.prefixes.last().map_or(true, |p| p.get() != prefix) {
+                self.prefixes.push(Row::new(row.get().slice_ref(prefix)));
+            }
+        }
+        self.rows.push(row);
+    }
+
+    pub fn build(mut self) -> Filter {
+        self.rows.append(&mut self.prefixes);
+        let rows = self.rows;
+        let size = self.size;
+        // a table may consist only of range tombstones
+        let n = rows.len().max(1);
+        let bloom_bits = || match size {
+            FilterSize::FalsePositiveRate(p) => compute_filter_bits(n, p) as u64,
+            FilterSize::BitsPerKey(b) => (n as f64 * b).ceil().max(1.0) as u64,
+        };
+        let bloom = |rows: &[Row]| {
+            let mut filter = BloomFilter::new(n as u64, bloom_bits());
+            for row in rows {
+                filter.insert(row);
+            }
+            Membership::Bloom(filter)
+        };
+        let membership = match self.kind {
+            FilterKind::Bloom => bloom(&rows),
+            FilterKind::BlockedBloom => {
+                let mut filter = BlockedBloomFilter::new(n as u64, bloom_bits());
+                for row in rows {
+                    filter.insert(row);
+                }
+                Membership::BlockedBloom(filter)
+            }
+            FilterKind::Cuckoo => {
+                let bits = match size {
+                    // a lookup compares the fingerprint with the 8 slots of two buckets
+                    FilterSize::FalsePositiveRate(p) => fingerprint_bits(p / 8.0),
+                    FilterSize::BitsPerKey(b) => clamp_bits(b * cuckoo_filter::LOAD_FACTOR),
+                };
+                match CuckooFilter::build(&rows, bits) {
+                    Some(filter) => Membership::Cuckoo(filter),
+                    // only if many rows collide, since equal rows are inserted once
+                    None => bloom(&rows),
+                }
+            }
+            FilterKind::Xor => {
+                let bits = match size {
+                    FilterSize::FalsePositiveRate(p) => fingerprint_bits(p),
+                    FilterSize::BitsPerKey(b) => clamp_bits(b / xor_filter::SPACE_FACTOR),
+                };
+                Membership::Xor(XorFilter::build(&rows, bits))
+            }
+            FilterKind::None => Membership::None,
+        };
+        Filter {
+            membership,
+            prefix_extractor: self.prefix_extractor,
+        }
+    }
+}
+
+/// Number of bits of a fingerprint which collides with a probability of `p`
+fn fingerprint_bits(p: f64) -> u32 {
+    clamp_bits((-p.log2()).ceil())
+}
+
+/// Number of bits of a fingerprint within a budget of `bits`
+fn clamp_bits(bits: f64) -> u32 {
+    bits.floor().max(1.0).min(32.0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kinds() {
+        let p = 0.01;
+        let n = 1000;
+        let rows = (0..n)
+            .map(|i| Row::new(Bytes::from(format!("row{:04}", i))))
+            .collect::<Vec<_>>();
+        for &kind in [
+            FilterKind::Bloom,
+            FilterKind::BlockedBloom,
+            FilterKind::Cuckoo,
+            FilterKind::Xor,
+        ]
+        .iter()
+        {
+            let mut builder = FilterBuilder::new(kind, FilterSize::FalsePositiveRate(p));
+            for row in rows.iter() {
+                builder.insert(row.clone());
+            }
+            let filter = builder.build();
+            assert_eq!(kind, filter.kind());
+            assert_eq!(Some(kind), FilterKind::from_id(kind.id()));
+            assert!(rows.iter().all(|row| filter.contains(row)));
+            assert!(
+                filter.false_positive_rate(n) < p * 1.5,
+                "kind={:?}, fpr={}",
+                kind,
+                filter.false_positive_rate(n)
+            );
+
+            let restored = Filter::from_bytes(
+                kind,
+                filter.k(),
+                filter.m() as u64,
+                &filter.to_bytes(),
+                None,
+            )
+            .unwrap();
+            assert_eq!(filter.to_bytes(), restored.to_bytes());
+            assert!(rows.iter().all(|row| restored.contains(row)));
+        }
+    }
+
+    #[test]
+    fn test_prefix() {
+        let rows = ["a:1", "a:2", "b", "bb:1", "c:1:2"];
+        for &kind in [FilterKind::Bloom, FilterKind::Xor].iter() {
+            let mut builder = FilterBuilder::new(kind, FilterSize::default())
+                .with_prefix_extractor(Some(PrefixExtractor::Delimiter(':')));
+            for row in rows.iter() {
+                builder.insert(Row::new(Bytes::from(*row)));
+            }
+            let filter = builder.build();
+
+            assert!(filter.may_contain_prefix(b"a:"));
+            assert!(filter.may_contain_prefix(b"a:1"));
+            assert!(filter.may_contain_prefix(b"c:"));
+            assert!(filter.may_contain_prefix(b"c:1:"));
+            assert!(!filter.may_contain_prefix(b"d:"));
+            assert!(!filter.may_contain_prefix(b"b:"));
+            // prefixes without the delimiter cannot be answered
+            assert!(filter.may_contain_prefix(b"d"));
+        }
+    }
+
+    #[test]
+    fn test_bits_per_key() {
+        let n = 1000;
+        let rows = (0..n)
+            .map(|i| Row::new(Bytes::from(format!("row{:04}", i))))
+            .collect::<Vec<_>>();
+        for &(kind, bits) in [
+            (FilterKind::Bloom, 10.0),
+            (FilterKind::BlockedBloom, 10.0),
+            (FilterKind::Cuckoo, 16.0),
+            (FilterKind::Xor, 10.0),
+        ]
+        .iter()
+        {
+            let mut builder = FilterBuilder::new(kind, FilterSize::BitsPerKey(bits));
+            for row in rows.iter() {
+                builder.insert(row.clone());
+            }
+            let filter = builder.build();
+            let bits_per_key = filter.m() as f64 / n as f64;
+            assert!(
+                bits_per_key <= bits * 1.1,
+                "kind={:?}, bits_per_key={}",
+                kind,
+                bits_per_key
+            );
+            assert!(rows.iter().all(|row| filter.contains(row)));
+        }
+    }
+
+    #[test]
+    fn test_none() {
+        let mut builder = FilterBuilder::new(FilterKind::None, FilterSize::default());
+        builder.insert(Row::new(Bytes::from("a")));
+        let filter = builder.build();
+        assert_eq!(0, filter.m());
+        assert!(filter.contains(&Row::new(Bytes::from("b"))));
+
+        let restored = Filter::from_bytes(FilterKind::None, 0, 0, &[], None).unwrap();
+        assert_eq!(FilterKind::None, restored.kind());
+        assert!(Filter::from_bytes(FilterKind::None, 0, 8, &[0], None).is_none());
+    }
+
+    #[test]
+    fn test_bloom_from_bytes() {
+        for &kind in &[FilterKind::Bloom, FilterKind::BlockedBloom] {
+            assert!(Filter::from_bytes(kind, 1, 0, &[], None).is_none());
+            assert!(Filter::from_bytes(kind, 0, 8, &[0], None).is_none());
+            assert!(Filter::from_bytes(kind, 65, 8, &[0], None).is_none());
+            assert!(Filter::from_bytes(kind, 64, 8, &[0], None).is_some());
+
+            // filters with many bits per row are still restored
+            let mut builder = FilterBuilder::new(kind, FilterSize::BitsPerKey(1000.0));
+            builder.insert(Row::new(Bytes::from("a")));
+            let filter = builder.build();
+            assert_eq!(bloom_filter::MAX_K, filter.k());
+            let bytes = filter.to_bytes();
+            assert!(
+                Filter::from_bytes(kind, filter.k(), filter.m() as u64, &bytes, None).is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_bits() {
+        assert_eq!(7, fingerprint_bits(0.01));
+        assert_eq!(8, fingerprint_bits(1.0 / 256.0));
+        assert_eq!(1, fingerprint_bits(0.9));
+        assert_eq!(32, fingerprint_bits(1e-12));
     }
 }
@@ -1,5 +1,6 @@
 use crate::model::{Key, Row};
 
+/// A sparse index holding the first key of each restart interval of the data.
 #[derive(Clone, Default)]
 pub struct Index {
     index: Vec<(Key, usize)>,
@@ -8,37 +9,41 @@ pub struct Index {
 impl Index {
     pub fn new(index: Vec<(Key, usize)>) -> Self {
         // keys are sorted
-        debug_assert!(index.len() > 0);
-
         Self { index }
     }
 
-    pub fn get_latest(&self, row: &Row) -> Option<(&Key, usize)> {
-        let key = Key::new(row.clone(), u64::max_value());
+    /// Number of restart points
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
 
-        let mut left = usize::max_value();
-        let mut right = self.index.len();
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
 
-        while right.wrapping_sub(left) > 1 {
-            let m = left.wrapping_add(right.wrapping_sub(left) / 2);
-            let k = &self.index[m].0;
+    pub fn iter(&self) -> impl Iterator<Item = &(Key, usize)> {
+        self.index.iter()
+    }
 
-            if &key <= k {
-                right = m;
+    /// Returns the offset of the restart point to look up the versions of `row` from.
+    pub fn seek(&self, row: &Row) -> usize {
+        let key = Key::new(row.clone(), u64::max_value());
+
+        // find the last restart point whose key is less than or equal to `key`
+        let mut left = 0;
+        let mut right = self.index.len();
+        while left < right {
+            let m = left + (right - left) / 2;
+            if self.index[m].0 <= key {
+                left = m + 1;
             } else {
-                left = m;
+                right = m;
             }
         }
 
-        if right < self.index.len() {
-            let (ref k, offset) = self.index[right];
-            if k.row() == row {
-                Some((k, offset))
-            } else {
-                None
-            }
-        } else {
-            None
+        match left {
+            0 => 0,
+            i => self.index[i - 1].1,
         }
     }
 }
@@ -48,33 +53,30 @@ mod tests {
     use super::*;
     use bytes::Bytes;
     #[test]
-    fn test_get_latest() {
+    fn test_seek() {
         let idx = vec![
             (Key::new(Row::new(Bytes::from("b")), 100), 0),
-            (Key::new(Row::new(Bytes::from("b")), 99), 1),
-            (Key::new(Row::new(Bytes::from("c")), 200), 2),
-            (Key::new(Row::new(Bytes::from("d")), 100), 3),
-            (Key::new(Row::new(Bytes::from("d")), 99), 4),
-            (Key::new(Row::new(Bytes::from("d")), 98), 5),
+            (Key::new(Row::new(Bytes::from("b")), 99), 10),
+            (Key::new(Row::new(Bytes::from("c")), 200), 20),
+            (Key::new(Row::new(Bytes::from("d")), 100), 30),
+            (Key::new(Row::new(Bytes::from("d")), 98), 40),
         ];
-        let index = Index::new(idx.clone());
-
-        let (k, o) = index.get_latest(&Row::new(Bytes::from("a"))).unwrap();
-        assert_eq!(&idx[0].0, k);
-        assert_eq!(idx[0].1, o);
+        let index = Index::new(idx);
 
-        let (k, o) = index.get_latest(&Row::new(Bytes::from("b"))).unwrap();
-        assert_eq!(&idx[0].0, k);
-        assert_eq!(idx[0].1, o);
-
-        let (k, o) = index.get_latest(&Row::new(Bytes::from("c"))).unwrap();
-        assert_eq!(&idx[2].0, k);
-        assert_eq!(idx[2].1, o);
-
-        let (k, o) = index.get_latest(&Row::new(Bytes::from("d"))).unwrap();
-        assert_eq!(&idx[3].0, k);
-        assert_eq!(idx[3].1, o);
+        // the latest version of a row may be the first key of the next restart interval
+        let cases = vec![
+            ("a", 0),
+            ("b", 0),
+            ("bb", 10),
+            ("c", 10),
+            ("d", 20),
+            ("e", 40),
+        ];
+        for (row, expected) in cases {
+            let actual = index.seek(&Row::new(Bytes::from(row)));
+            assert_eq!(expected, actual, "row={}", row);
+        }
 
-        assert!(index.get_latest(&Row::new(Bytes::from("e"))).is_none());
+        assert_eq!(0, Index::default().seek(&Row::new(Bytes::from("a"))));
     }
 }
@@ -1,75 +1,170 @@
+mod compression;
 mod data;
+pub mod file;
 mod filter;
 mod index;
+mod prefix;
 
-use self::data::DataBuilder;
-pub use self::{data::Data, filter::Filter, index::Index};
-use super::{compacter::SingleVersionCompacter, stat::Stat};
-use crate::{
-    collections::bloom_filter::BloomFilter,
-    model::{Key, Row, Value},
+use self::data::{DataBuilder, RESTART_INTERVAL};
+pub use self::{
+    compression::Compression,
+    data::{Data, RecordFormat},
+    filter::{Filter, FilterBuilder, FilterKind, FilterSize},
+    index::Index,
+    prefix::PrefixExtractor,
 };
-use std::f64::consts::LN_2;
+use super::{stat::Stat, tombstone::RangeTombstones};
+use crate::model::{Key, Value};
+use std::{error, f64::consts::LN_2, fmt};
 
 pub struct SSTable {
     pub data: Data,
     pub index: Index,
     pub filter: Filter,
+    pub tombstones: RangeTombstones,
+    /// Codec to compress the data blocks with when the table is written
+    pub compression: Compression,
 }
 
+/// Options of new SSTables built by the store.
+/// Tables in memory are never compressed; see [`SSTableBuilder::compression`] for files.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TableOptions {
+    pub filter: FilterKind,
+    pub filter_size: FilterSize,
+    pub prefix_extractor: Option<PrefixExtractor>,
+    /// Build no filter for tables produced by compactions, which hold most of the rows
+    /// and are read by most lookups anyway
+    pub skip_last_level_filters: bool,
+    // TODO: add the compression of the data blocks once the store writes tables to files
+}
+
+/// A builder of an SSTable from sorted keys.
+///
+/// Tables can be built offline with [`add`](Self::add) and [`finish`](Self::finish),
+/// written with [`file::encode`] and ingested into a running server.
+///
+/// ```
+/// use beatrice_server::{
+///     model::{Key, Row, Value},
+///     sstable::{file, SSTableBuilder},
+/// };
+/// use bytes::Bytes;
+///
+/// let mut builder = SSTableBuilder::with_capacity(2, 64);
+/// for (row, timestamp) in &[("a", 2), ("a", 1), ("b", 1)] {
+///     let key = Key::new(Row::new(Bytes::from(*row)), *timestamp);
+///     builder.add(key, Value::Val(Bytes::from("v"))).unwrap();
+/// }
+/// let buf = file::encode(&builder.finish());
+/// assert_eq!(3, file::decode(buf, true).unwrap().meta.entries);
+/// ```
 pub struct SSTableBuilder {
-    max_len: usize,
-    len: usize,
-    offset: usize,
+    last: Option<Key>,
     data: DataBuilder,
     index: Vec<(Key, usize)>,
-    filter: BloomFilter<Row>,
+    filter: FilterBuilder,
+    compression: Compression,
 }
 
 impl SSTableBuilder {
-    pub fn new(stat: &Stat, p: f64) -> Self {
-        let max_len = stat.len();
-        debug_assert!(max_len > 0);
-        let m = compute_filter_bits(max_len, p);
-        let data_size = stat.key_size() + stat.value_size();
+    pub(crate) fn new(stat: &Stat) -> Self {
+        Self::with_capacity(stat.len(), stat.key_size() + stat.value_size())
+    }
 
+    /// Creates a builder with room for `len` records of `data_size` bytes in total.
+    /// The capacities are only hints.
+    pub fn with_capacity(len: usize, data_size: usize) -> Self {
         Self {
-            max_len,
-            len: 0,
-            offset: 0,
+            last: None,
             data: DataBuilder::new(data_size),
-            index: Vec::with_capacity(max_len),
-            filter: BloomFilter::new(max_len as u64, m as u64),
+            index: Vec::with_capacity(len / RESTART_INTERVAL + 1),
+            filter: FilterBuilder::new(FilterKind::default(), FilterSize::default()),
+            compression: Compression::None,
         }
     }
 
-    pub fn load<I: IntoIterator<Item = (Key, Value)>>(mut self, iter: I) -> SSTable {
-        let mut iter = iter.into_iter();
-        let (key, value) = iter.next().unwrap();
-        let mut compacter = SingleVersionCompacter::new(key, value);
+    /// Sets the codec to compress the data block with when the table is written by [`file::encode`].
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn filter_kind(mut self, kind: FilterKind) -> Self {
+        self.filter = self.filter.with_kind(kind);
+        self
+    }
+
+    pub fn filter_size(mut self, size: FilterSize) -> Self {
+        self.filter = self.filter.with_size(size);
+        self
+    }
+
+    /// Inserts the prefixes of rows extracted by `prefix_extractor` into the filter as well.
+    pub fn prefix_extractor(mut self, prefix_extractor: Option<PrefixExtractor>) -> Self {
+        self.filter = self.filter.with_prefix_extractor(prefix_extractor);
+        self
+    }
+
+    pub fn options(self, options: &TableOptions) -> Self {
+        self.filter_kind(options.filter)
+            .filter_size(options.filter_size)
+            .prefix_extractor(options.prefix_extractor)
+    }
+
+    /// Builds a table from versions sorted by key.
+    /// Versions shadowed by deletions are kept as well, so that the table answers reads
+    /// at past timestamps.
+    pub fn load<I: IntoIterator<Item = (Key, Value)>>(
+        mut self,
+        iter: I,
+        tombstones: RangeTombstones,
+    ) -> SSTable {
         for (key, value) in iter {
-            if let Some((k, v)) = compacter.compact(key, value) {
-                self.append(k, v);
+            self.append(key, value);
+        }
+        self.build(tombstones)
+    }
+
+    /// Appends a version of a row.
+    /// Keys must be added in order, i.e. rows ascending and timestamps descending,
+    /// and each key at most once.
+    pub fn add(&mut self, key: Key, value: Value) -> Result<(), OutOfOrder> {
+        if let Some(last) = &self.last {
+            if last >= &key {
+                return Err(OutOfOrder {
+                    last: last.clone(),
+                    key,
+                });
             }
         }
-        let (k, v) = compacter.into_key_value();
-        self.append(k, v);
+        self.append(key, value);
+        Ok(())
+    }
 
+    /// Builds the table of the added records.
+    pub fn finish(self) -> SSTable {
+        self.build(RangeTombstones::default())
+    }
+
+    fn build(self, tombstones: RangeTombstones) -> SSTable {
         SSTable {
             data: self.data.build(),
             index: Index::new(self.index),
-            filter: Filter::new(self.filter),
+            filter: self.filter.build(),
+            tombstones,
+            compression: self.compression,
         }
     }
 
     fn append(&mut self, key: Key, value: Value) {
-        debug_assert!(self.len < self.max_len);
+        debug_assert!(self.last.as_ref().map_or(true, |last| last < &key));
 
+        self.last = Some(key.clone());
         self.append_filter(&key);
-        let size = self.data.append(key.clone(), value.clone());
-        self.append_index(key, self.offset);
-        self.len += 1;
-        self.offset += size;
+        if let Some(offset) = self.data.append(key.clone(), value) {
+            self.append_index(key, offset);
+        }
     }
 
     fn append_index(&mut self, key: Key, offset: usize) {
@@ -81,6 +176,28 @@ impl SSTableBuilder {
     }
 }
 
+/// An error of [`SSTableBuilder::add`] given a key not greater than the last one.
+#[derive(Debug)]
+pub struct OutOfOrder {
+    pub last: Key,
+    pub key: Key,
+}
+
+impl fmt::Display for OutOfOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "key {:?}@{} is added after {:?}@{}",
+            self.key.row().get(),
+            self.key.timestamp(),
+            self.last.row().get(),
+            self.last.timestamp()
+        )
+    }
+}
+
+impl error::Error for OutOfOrder {}
+
 fn compute_filter_bits(n: usize, p: f64) -> usize {
     // https://hur.st/bloomfilter
     // m = ceil((n * log(p)) / log(1 / pow(2, log(2))))
@@ -91,6 +208,55 @@ fn compute_filter_bits(n: usize, p: f64) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::Row;
+    use bytes::Bytes;
+
+    fn key(row: &'static str, timestamp: u64) -> Key {
+        Key::new(Row::new(Bytes::from(row)), timestamp)
+    }
+
+    #[test]
+    fn test_add() {
+        let mut builder = SSTableBuilder::with_capacity(0, 0);
+        for &(row, timestamp) in &[("a", 3), ("a", 1), ("b", 2)] {
+            builder.add(key(row, timestamp), Value::Del).unwrap();
+        }
+        assert!(builder.add(key("b", 2), Value::Del).is_err());
+        assert!(builder.add(key("b", 3), Value::Del).is_err());
+        assert!(builder.add(key("a", 0), Value::Del).is_err());
+        builder
+            .add(key("c", 1), Value::Val(Bytes::from("c1")))
+            .unwrap();
+
+        let sstable = builder.finish();
+        assert_eq!(4, sstable.data.len());
+        assert!(sstable.filter.contains(&Row::new(Bytes::from("c"))));
+        let c = Row::new(Bytes::from("c"));
+        assert_eq!(
+            Some((key("c", 1), Value::Val(Bytes::from("c1")))),
+            sstable.data.get_latest(&c, sstable.index.seek(&c))
+        );
+        let a = Row::new(Bytes::from("a"));
+        assert_eq!(
+            Some((key("a", 1), Value::Del)),
+            sstable.data.get_at(&a, 2, sstable.index.seek(&a))
+        );
+    }
+
+    #[test]
+    fn test_filter_of_versions() {
+        let a = Row::new(Bytes::from("a"));
+        for &kind in &[FilterKind::Bloom, FilterKind::Cuckoo, FilterKind::Xor] {
+            let mut builder = SSTableBuilder::with_capacity(100, 0).filter_kind(kind);
+            for timestamp in (1..=100).rev() {
+                builder.add(key("a", timestamp), Value::Del).unwrap();
+            }
+            let sstable = builder.finish();
+            assert_eq!(kind, sstable.filter.kind());
+            assert!(sstable.filter.contains(&a));
+        }
+    }
+
     #[test]
     fn test_compute_filter_bits() {
         let cases = vec![((10, 1e-3), 144), ((1000, 1e-4), 19171)];
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+/// Extracts the prefixes of rows inserted into the filter of an SSTable
+/// so that prefix scans can skip tables without matching rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefixExtractor {
+    /// The first `n` bytes. Rows shorter than `n` bytes have no prefix.
+    Fixed(usize),
+    /// The bytes up to and including the first delimiter.
+    /// Rows without the delimiter have no prefix.
+    Delimiter(char),
+}
+
+impl PrefixExtractor {
+    const FIXED: u8 = 1;
+    const DELIMITER: u8 = 2;
+
+    pub fn extract<'a>(&self, row: &'a [u8]) -> Option<&'a [u8]> {
+        match *self {
+            PrefixExtractor::Fixed(n) => row.get(..n),
+            PrefixExtractor::Delimiter(c) => {
+                let mut buf = [0; 4];
+                let delimiter = c.encode_utf8(&mut buf).as_bytes();
+                row.windows(delimiter.len())
+                    .position(|w| w == delimiter)
+                    .map(|i| &row[..i + delimiter.len()])
+            }
+        }
+    }
+
+    /// Identifier of the kind and its parameter recorded in SSTable files
+    pub fn to_id(self) -> (u8, u64) {
+        match self {
+            PrefixExtractor::Fixed(n) => (Self::FIXED, n as u64),
+            PrefixExtractor::Delimiter(c) => (Self::DELIMITER, c as u64),
+        }
+    }
+
+    pub fn from_id(id: u8, param: u64) -> Option<Self> {
+        match id {
+            Self::FIXED => Some(PrefixExtractor::Fixed(param as usize)),
+            Self::DELIMITER => std::char::from_u32(param as u32).map(PrefixExtractor::Delimiter),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let fixed = PrefixExtractor::Fixed(4);
+        assert_eq!(Some(&b"user"[..]), fixed.extract(b"user:1"));
+        assert_eq!(Some(&b"user"[..]), fixed.extract(b"user"));
+        assert_eq!(None, fixed.extract(b"usr"));
+
+        let delimiter = PrefixExtractor::Delimiter(':');
+        assert_eq!(Some(&b"user:"[..]), delimiter.extract(b"user:1:a"));
+        assert_eq!(Some(&b"user:"[..]), delimiter.extract(b"user:"));
+        assert_eq!(None, delimiter.extract(b"user"));
+    }
+
+    #[test]
+    fn test_id() {
+        for &extractor in [PrefixExtractor::Fixed(8), PrefixExtractor::Delimiter('/')].iter() {
+            let (id, param) = extractor.to_id();
+            assert_eq!(Some(extractor), PrefixExtractor::from_id(id, param));
+        }
+        assert_eq!(None, PrefixExtractor::from_id(0, 0));
+    }
+}
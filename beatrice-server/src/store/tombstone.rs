@@ -0,0 +1,105 @@
+use crate::model::{RangeTombstone, Row};
+
+/// A set of range tombstones sorted by their start rows.
+#[derive(Clone, Default, Debug)]
+pub struct RangeTombstones {
+    tombstones: Vec<RangeTombstone>,
+}
+
+impl RangeTombstones {
+    pub fn insert(&mut self, tombstone: RangeTombstone) {
+        let i = self
+            .tombstones
+            .iter()
+            .position(|t| t.start() > tombstone.start())
+            .unwrap_or(self.tombstones.len());
+        self.tombstones.insert(i, tombstone);
+    }
+
+    pub fn len(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tombstones.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RangeTombstone> {
+        self.tombstones.iter()
+    }
+
+    /// Returns the largest timestamp of the tombstones covering `row`.
+    pub fn get_latest(&self, row: &Row) -> Option<u64> {
+        self.get_latest_at(row, u64::max_value())
+    }
+
+    /// Returns the largest timestamp up to `timestamp` of the tombstones covering `row`.
+    pub fn get_latest_at(&self, row: &Row, timestamp: u64) -> Option<u64> {
+        self.tombstones
+            .iter()
+            .take_while(|t| t.start() <= row)
+            .filter(|t| t.contains(row) && t.timestamp() <= timestamp)
+            .map(|t| t.timestamp())
+            .max()
+    }
+
+    /// Returns true if a version of `row` written at `timestamp` is deleted.
+    pub fn covers(&self, row: &Row, timestamp: u64) -> bool {
+        self.get_latest(row).map_or(false, |t| timestamp <= t)
+    }
+}
+
+impl Extend<RangeTombstone> for RangeTombstones {
+    fn extend<I: IntoIterator<Item = RangeTombstone>>(&mut self, iter: I) {
+        for tombstone in iter {
+            self.insert(tombstone);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn tombstone(start: &'static str, end: &'static str, timestamp: u64) -> RangeTombstone {
+        RangeTombstone::new(
+            Row::new(Bytes::from(start)),
+            Row::new(Bytes::from(end)),
+            timestamp,
+        )
+    }
+
+    #[test]
+    fn test_get_latest() {
+        let mut tombstones = RangeTombstones::default();
+        tombstones.insert(tombstone("c", "f", 20));
+        tombstones.insert(tombstone("a", "d", 10));
+        tombstones.insert(tombstone("e", "g", 5));
+
+        let cases = vec![
+            ("0", None),
+            ("a", Some(10)),
+            ("c", Some(20)),
+            ("d", Some(20)),
+            ("e", Some(20)),
+            ("f", Some(5)),
+            ("g", None),
+        ];
+        for (row, expected) in cases {
+            let actual = tombstones.get_latest(&Row::new(Bytes::from(row)));
+            assert_eq!(expected, actual, "row={}", row);
+        }
+    }
+
+    #[test]
+    fn test_covers() {
+        let mut tombstones = RangeTombstones::default();
+        tombstones.insert(tombstone("a", "c", 10));
+
+        let row = Row::new(Bytes::from("b"));
+        assert!(tombstones.covers(&row, 9));
+        assert!(tombstones.covers(&row, 10));
+        assert!(!tombstones.covers(&row, 11));
+    }
+}
@@ -1,11 +1,17 @@
 use super::{
-    sstable::{SSTable, SSTableBuilder},
+    sstable::{SSTable, SSTableBuilder, TableOptions},
     stat::Stat,
+    tombstone::RangeTombstones,
 };
 use crate::{
-    collections::skip_list::SkipListMap,
-    model::{Key, Row, Value},
+    collections::{bloom_filter::CountingBloomFilter, skip_list::SkipListMap},
+    model::{Key, RangeTombstone, Row, Value},
 };
+use bytes::Bytes;
+use std::cmp;
+
+/// Number of counters per row in the negative cache
+const FILTER_COUNTERS_PER_ROW: usize = 10;
 
 fn log2(x: usize) -> usize {
     ((std::mem::size_of::<usize>() * 8) as usize) - (x.leading_zeros() as usize) - 1
@@ -14,6 +20,14 @@ fn log2(x: usize) -> usize {
 pub struct VolatileStore {
     level: usize,
     map: SkipListMap<Key, Value>,
+    /// Negative cache of the rows in `map`
+    filter: CountingBloomFilter<Row>,
+    /// Expected number of rows, grown on `clear` to the number of rows seen
+    capacity: usize,
+    rows: usize,
+    tombstones: RangeTombstones,
+    /// Total size of `tombstones` in bytes
+    tombstone_size: usize,
     stat: Stat,
 }
 
@@ -23,12 +37,42 @@ impl VolatileStore {
         Self {
             level,
             map: SkipListMap::new(level),
+            filter: new_filter(n),
+            capacity: n,
+            rows: 0,
+            tombstones: RangeTombstones::default(),
+            tombstone_size: 0,
             stat: Stat::default(),
         }
     }
 
-    pub fn get_latest(&self, row: &Row) -> Option<(&Key, &Value)> {
-        let key = Key::new(row.clone(), u64::max_value());
+    /// Sets the number of levels of the skip list, which is derived from the number of rows by default.
+    pub fn with_level(mut self, level: usize) -> Self {
+        debug_assert!(self.is_empty());
+        self.level = level;
+        self.map = SkipListMap::new(level);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.stat.len()
+    }
+
+    /// Returns the total size of the entries and the range tombstones in bytes.
+    pub fn size(&self) -> usize {
+        self.stat.key_size() + self.stat.value_size() + self.tombstone_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stat.len() == 0 && self.tombstones.is_empty()
+    }
+
+    /// Returns the latest version of `row` written at or before `timestamp`.
+    pub fn get_at(&self, row: &Row, timestamp: u64) -> Option<(&Key, &Value)> {
+        if !self.filter.contains(row) {
+            return None;
+        }
+        let key = Key::new(row.clone(), timestamp);
         let kv = self.map.get_smallest_key_value(&key);
 
         kv.and_then(|(k, v)| {
@@ -40,19 +84,118 @@ impl VolatileStore {
         })
     }
 
+    /// Returns all the versions of `row` from the latest to the oldest.
+    pub fn get_versions<'a>(
+        &'a self,
+        row: &'a Row,
+    ) -> impl Iterator<Item = (&'a Key, &'a Value)> + 'a {
+        let key = Key::new(row.clone(), u64::max_value());
+        self.map
+            .iter_from(&key)
+            .take_while(move |(k, _)| k.row() == row)
+    }
+
+    /// Returns all the versions of the rows starting with `prefix` from `start` in order.
+    pub fn scan_prefix<'a>(
+        &'a self,
+        prefix: &'a [u8],
+        start: &[u8],
+    ) -> impl Iterator<Item = (&'a Key, &'a Value)> + 'a {
+        let from = cmp::max(prefix, start);
+        let key = Key::new(Row::new(Bytes::copy_from_slice(from)), u64::max_value());
+        self.map
+            .iter_from(&key)
+            .take_while(move |(k, _)| k.row().get().starts_with(prefix))
+    }
+
     pub fn insert(&mut self, row: Row, timestamp: u64, value: Value) {
         let key = Key::new(row, timestamp);
-        let old_value = self.map.insert(key.clone(), value.clone());
+        let (old_value, prev, next) = self.map.insert_with_neighbors(key.clone(), value.clone());
+        // Versions of a row are adjacent, so the row is new unless a neighbor shares it.
+        let same_row = |k: Option<&Key>| k.map_or(false, |k| k.row() == key.row());
+        let new_row = old_value.is_none() && !same_row(prev) && !same_row(next);
         self.stat.insert(&key, &value, old_value.as_ref());
+        // Count each row once so that counters saturate only with the number of rows.
+        if new_row {
+            self.filter.insert(key.row());
+            self.rows += 1;
+        }
+    }
+
+    pub fn insert_tombstone(&mut self, tombstone: RangeTombstone) {
+        self.tombstone_size += tombstone.size();
+        self.tombstones.insert(tombstone);
+    }
+
+    pub fn get_latest_tombstone(&self, row: &Row) -> Option<u64> {
+        self.tombstones.get_latest(row)
+    }
+
+    pub fn get_latest_tombstone_at(&self, row: &Row, timestamp: u64) -> Option<u64> {
+        self.tombstones.get_latest_at(row, timestamp)
+    }
+
+    pub fn stat(&self) -> &Stat {
+        &self.stat
+    }
+
+    pub fn tombstones(&self) -> &RangeTombstones {
+        &self.tombstones
     }
 
-    pub fn flush(&self) -> SSTable {
-        let builder = SSTableBuilder::new(&self.stat, 0.001);
-        builder.load(self.map.iter().map(|(k, v)| (k.clone(), v.clone())))
+    pub fn flush(&self, options: &TableOptions) -> SSTable {
+        let builder = SSTableBuilder::new(&self.stat).options(options);
+        builder.load(
+            self.map.iter().map(|(k, v)| (k.clone(), v.clone())),
+            self.tombstones.clone(),
+        )
     }
 
     pub fn clear(&mut self) {
         self.map = SkipListMap::new(self.level);
+        self.capacity = self.capacity.max(self.rows);
+        self.filter = new_filter(self.capacity);
+        self.rows = 0;
+        self.tombstones = RangeTombstones::default();
+        self.tombstone_size = 0;
         self.stat = Stat::default();
     }
 }
+
+fn new_filter(n: usize) -> CountingBloomFilter<Row> {
+    let n = n.max(1);
+    CountingBloomFilter::new(n as u64, (n * FILTER_COUNTERS_PER_ROW) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_cache() {
+        let mut store = VolatileStore::new(4);
+        for i in 0..16 {
+            let row = Row::new(Bytes::from(format!("row{:02}", i)));
+            store.insert(row.clone(), 1, Value::Val(Bytes::from("a")));
+            store.insert(row, 2, Value::Val(Bytes::from("b")));
+        }
+        assert_eq!(16, store.rows);
+        // an older version lands after the others of the row
+        store.insert(Row::new(Bytes::from("row07")), 0, Value::Del);
+        assert_eq!(16, store.rows);
+        for i in 0..16 {
+            let row = Row::new(Bytes::from(format!("row{:02}", i)));
+            assert_eq!(
+                2,
+                store.get_at(&row, u64::max_value()).unwrap().0.timestamp()
+            );
+        }
+
+        store.clear();
+        assert_eq!(16, store.capacity);
+        assert_eq!(160, store.filter.m());
+        let row = Row::new(Bytes::from("row00"));
+        assert!(store.get_at(&row, u64::max_value()).is_none());
+        assert!(!store.filter.contains(&row));
+    }
+}
@@ -0,0 +1,97 @@
+//! LEB128 variable-length encoding of unsigned integers.
+
+use bytes::{Buf, BufMut};
+
+/// Maximum size of an encoded `u64`
+pub const MAX_SIZE: usize = 10;
+
+pub fn size(mut v: u64) -> usize {
+    let mut size = 1;
+    while v >= 0x80 {
+        v >>= 7;
+        size += 1;
+    }
+    size
+}
+
+pub fn put<B: BufMut>(buf: &mut B, mut v: u64) -> usize {
+    let mut size = 1;
+    while v >= 0x80 {
+        buf.put_u8((v as u8) | 0x80);
+        v >>= 7;
+        size += 1;
+    }
+    buf.put_u8(v as u8);
+    size
+}
+
+/// Maps signed integers to unsigned ones so that small magnitudes have short encodings.
+pub fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+pub fn unzigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+/// Reads a varint from `buf`.
+/// Returns `None` if `buf` is truncated or the varint overflows `u64`.
+pub fn get<B: Buf>(buf: &mut B) -> Option<u64> {
+    let mut v = 0u64;
+    for i in 0..MAX_SIZE {
+        if !buf.has_remaining() {
+            return None;
+        }
+        let b = buf.get_u8();
+        if i == MAX_SIZE - 1 && b > 1 {
+            return None;
+        }
+        v |= ((b & 0x7f) as u64) << (7 * i);
+        if b < 0x80 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_put_get() {
+        for &v in &[0, 1, 127, 128, 300, 1 << 35, u64::max_value()] {
+            let mut buf = BytesMut::new();
+            let n = put(&mut buf, v);
+            assert_eq!(size(v), n);
+            assert_eq!(n, buf.len());
+
+            let mut buf = buf.freeze();
+            assert_eq!(Some(v), get(&mut buf), "v={}", v);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_zigzag() {
+        let cases = vec![
+            (0, 0),
+            (-1, 1),
+            (1, 2),
+            (-2, 3),
+            (i64::max_value(), u64::max_value() - 1),
+        ];
+        for (v, expected) in cases {
+            assert_eq!(expected, zigzag(v));
+            assert_eq!(v, unzigzag(expected));
+        }
+        assert_eq!(i64::min_value(), unzigzag(zigzag(i64::min_value())));
+    }
+
+    #[test]
+    fn test_get_malformed() {
+        assert_eq!(None, get(&mut &[0x80, 0x80][..]));
+        assert_eq!(None, get(&mut &[0xff; 10][..]));
+    }
+}